@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use log::{error, info};
+use serde::Serialize;
+
+use crate::scenarios::ScenarioResult;
+
+/// Minimum time between two alerts for the same scenario
+const DEBOUNCE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A failed scenario to alert about
+#[derive(Serialize)]
+pub(crate) struct Alert<'a> {
+    scenario: &'a str,
+    status: &'a str,
+    start_ts: u64,
+    /// How many times in a row the scenario failed
+    consecutive_failures: u64,
+}
+
+/// Where alerts are delivered to
+#[async_trait]
+pub(crate) trait AlertChannel: Send + Sync {
+    fn name(&self) -> &str;
+
+    async fn send(&self, alert: &Alert<'_>) -> Result<()>;
+}
+
+/// Sends alerts for failed scenarios to all configured channels
+pub(crate) struct Alerts {
+    channels: Vec<Box<dyn AlertChannel>>,
+    /// When an alert was last sent, per scenario
+    last_sent: HashMap<String, Instant>,
+}
+
+impl Alerts {
+    pub(crate) fn new(channels: Vec<Box<dyn AlertChannel>>) -> Self {
+        Self {
+            channels,
+            last_sent: HashMap::new(),
+        }
+    }
+
+    /// Alert if the scenario failed, unless an alert was already sent for this scenario within the
+    /// [DEBOUNCE_INTERVAL].
+    ///
+    /// Delivery errors are only logged.
+    pub(crate) async fn notify(
+        &mut self,
+        res: &ScenarioResult,
+        start_ts: u64,
+        consecutive_failures: u64,
+    ) {
+        if self.channels.is_empty() || !res.measurement.is_failed() {
+            return;
+        }
+        if let Some(last_sent) = self.last_sent.get(&res.name) {
+            if last_sent.elapsed() < DEBOUNCE_INTERVAL {
+                info!("[{}] Skipping alert, recently sent", res.name);
+                return;
+            }
+        }
+
+        let alert = Alert {
+            scenario: &res.name,
+            status: &res.measurement.status,
+            start_ts,
+            consecutive_failures,
+        };
+        for channel in &self.channels {
+            if let Err(e) = channel.send(&alert).await {
+                error!(
+                    "[{}] Failed to send {} alert: {e}",
+                    res.name,
+                    channel.name()
+                );
+            }
+        }
+        self.last_sent.insert(res.name.clone(), Instant::now());
+    }
+}
+
+/// POSTs the alert as JSON to a webhook
+pub(crate) struct Webhook {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl Webhook {
+    pub(crate) fn new(url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl AlertChannel for Webhook {
+    fn name(&self) -> &str {
+        "webhook"
+    }
+
+    async fn send(&self, alert: &Alert<'_>) -> Result<()> {
+        self.client
+            .post(&self.url)
+            .json(alert)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Sends the alert as a message to a Telegram chat, through the Bot API
+pub(crate) struct Telegram {
+    client: reqwest::Client,
+    bot_token: String,
+    chat_id: String,
+}
+
+impl Telegram {
+    pub(crate) fn new(bot_token: &str, chat_id: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            bot_token: bot_token.into(),
+            chat_id: chat_id.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl AlertChannel for Telegram {
+    fn name(&self) -> &str {
+        "Telegram"
+    }
+
+    async fn send(&self, alert: &Alert<'_>) -> Result<()> {
+        let text = format!(
+            "sdk_pulse: {} failed ({} in a row)\nStatus: {}\nIteration start: {}",
+            alert.scenario, alert.consecutive_failures, alert.status, alert.start_ts
+        );
+        self.client
+            .post(format!(
+                "https://api.telegram.org/bot{}/sendMessage",
+                self.bot_token
+            ))
+            .json(&serde_json::json!({
+                "chat_id": self.chat_id,
+                "text": text,
+            }))
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+/// Delay before retrying after the `attempt`-th failure (starting at 0): a random duration between
+/// 0 and `min(cap, base * 2^attempt)`.
+///
+/// The randomness ("full jitter") keeps retries of multiple clients from lining up.
+pub(crate) fn backoff_delay(attempt: u32, base: Duration, cap: Duration) -> Duration {
+    let exp = 2u32.checked_pow(attempt).unwrap_or(u32::MAX);
+    let max_delay = base.saturating_mul(exp).min(cap);
+    max_delay.mul_f64(rand::thread_rng().gen_range(0.0..=1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: Duration = Duration::from_millis(100);
+    const CAP: Duration = Duration::from_secs(5);
+
+    #[test]
+    fn delay_stays_below_the_growing_upper_bound() {
+        for attempt in 0..10 {
+            let upper_bound = (BASE * 2u32.pow(attempt)).min(CAP);
+            for _ in 0..1000 {
+                assert!(backoff_delay(attempt, BASE, CAP) <= upper_bound);
+            }
+        }
+    }
+
+    #[test]
+    fn delay_is_jittered_over_the_whole_range() {
+        // With a fixed upper bound of 800ms, 1000 samples cover both ends of the range
+        let delays: Vec<_> = (0..1000).map(|_| backoff_delay(3, BASE, CAP)).collect();
+        assert!(delays
+            .iter()
+            .any(|delay| *delay < Duration::from_millis(200)));
+        assert!(delays
+            .iter()
+            .any(|delay| *delay > Duration::from_millis(600)));
+    }
+
+    #[test]
+    fn large_attempt_saturates_to_cap() {
+        for attempt in [31, 32, 100, u32::MAX] {
+            for _ in 0..100 {
+                assert!(backoff_delay(attempt, BASE, CAP) <= CAP);
+            }
+        }
+        // The upper bound is the cap, so some delays come close to it
+        assert!((0..1000).any(|_| backoff_delay(u32::MAX, BASE, CAP) > CAP / 2));
+    }
+}
@@ -0,0 +1,63 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use figment::providers::{Format, Toml};
+use figment::Figment;
+use log::{error, info};
+use serde::Deserialize;
+
+use crate::stats::Stats;
+
+/// Expected latencies per scenario, read from a TOML file like
+///
+/// ```toml
+/// [p90_ms]
+/// gl2gl = 2000
+/// gl2wos = 3500
+/// ```
+#[derive(Debug, Deserialize)]
+pub(crate) struct Baseline {
+    /// Expected p90 duration per scenario name, in milliseconds
+    p90_ms: HashMap<String, u64>,
+}
+
+impl Baseline {
+    pub(crate) fn load(path: &str) -> Result<Self> {
+        if !Path::new(path).exists() {
+            return Err(anyhow!("Baseline file {path} not found"));
+        }
+        Ok(Figment::from(Toml::file(path)).extract()?)
+    }
+
+    /// Compare the p90 of every scenario in the baseline with the one of this run.
+    ///
+    /// Returns an error listing the scenarios whose p90 exceeds the baseline times `factor`.
+    /// Scenarios without successful runs are not compared.
+    pub(crate) fn check(&self, stats: &Stats, factor: f64) -> Result<()> {
+        let mut regressions = vec![];
+        for (name, baseline_ms) in &self.p90_ms {
+            let Some(p90_ms) = stats
+                .scenario(name)
+                .and_then(|scenario_stats| scenario_stats.percentile_ms(90.0))
+            else {
+                info!("{name}: no successful runs to compare with the baseline");
+                continue;
+            };
+
+            let limit_ms = *baseline_ms as f64 * factor;
+            if p90_ms as f64 > limit_ms {
+                error!("{name}: p90 {p90_ms}ms exceeds the baseline {baseline_ms}ms x {factor}");
+                regressions.push(name.as_str());
+            } else {
+                info!("{name}: p90 {p90_ms}ms within the baseline {baseline_ms}ms x {factor}");
+            }
+        }
+
+        if !regressions.is_empty() {
+            regressions.sort_unstable();
+            return Err(anyhow!("Latency regressed for {}", regressions.join(", ")));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,83 @@
+use std::path::Path;
+
+use anyhow::Result;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::stats::Stats;
+
+/// Progress of a run bounded by `max_iterations`, saved after every recorded iteration so an
+/// interrupted run can resume where it left off
+#[derive(Deserialize, Serialize)]
+pub(crate) struct Checkpoint {
+    /// Names of the configured scenarios and the iteration limit, to only resume the same run
+    scenarios: Vec<String>,
+    max_iterations: u64,
+    /// Number of recorded iterations, not counting warm-up iterations
+    pub(crate) completed: u64,
+    pub(crate) stats: Stats,
+}
+
+impl Checkpoint {
+    /// Read the checkpoint at `path`, if there is one of a run with the same scenarios, iteration
+    /// limit and latency buckets
+    pub(crate) fn load(
+        path: &str,
+        scenarios: &[String],
+        max_iterations: u64,
+        bucket_bounds_secs: &[f64],
+    ) -> Result<Option<Self>> {
+        if !Path::new(path).exists() {
+            return Ok(None);
+        }
+        let checkpoint: Self = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        if checkpoint.scenarios != scenarios
+            || checkpoint.max_iterations != max_iterations
+            || checkpoint.stats.bucket_bounds_secs() != bucket_bounds_secs
+        {
+            warn!("Checkpoint {path} is of a run with a different config, ignoring it");
+            return Ok(None);
+        }
+        info!(
+            "Resuming from checkpoint {path} after {}/{max_iterations} iterations",
+            checkpoint.completed
+        );
+        Ok(Some(checkpoint))
+    }
+
+    /// Write the progress to `path`, replacing the previous checkpoint only once it's fully written
+    pub(crate) fn save(
+        path: &str,
+        scenarios: &[String],
+        max_iterations: u64,
+        completed: u64,
+        stats: &Stats,
+    ) -> Result<()> {
+        #[derive(Serialize)]
+        struct CheckpointRef<'a> {
+            scenarios: &'a [String],
+            max_iterations: u64,
+            completed: u64,
+            stats: &'a Stats,
+        }
+
+        let tmp_path = format!("{path}.tmp");
+        let checkpoint = CheckpointRef {
+            scenarios,
+            max_iterations,
+            completed,
+            stats,
+        };
+        std::fs::write(&tmp_path, serde_json::to_vec(&checkpoint)?)?;
+        std::fs::rename(tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Delete the checkpoint at `path`, once the run completed
+    pub(crate) fn remove(path: &str) -> Result<()> {
+        if Path::new(path).exists() {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use log::error;
+use serde_json::{json, Value};
+
+use crate::sinks::Sink;
+use crate::IterationResult;
+
+/// Datadog site used if none is configured
+pub(crate) const DEFAULT_DATADOG_SITE: &str = "datadoghq.com";
+
+/// Datadog metric type of a gauge, in the v2 series API
+const GAUGE: u8 = 3;
+
+/// Submits the payment durations and outcomes to Datadog, through the metrics API
+pub(crate) struct DatadogSink {
+    client: reqwest::Client,
+    series_url: String,
+    api_key: String,
+}
+
+impl DatadogSink {
+    /// `site` is the Datadog site of the account, like `datadoghq.com` or `datadoghq.eu`
+    pub(crate) fn new(api_key: &str, site: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            series_url: format!("https://api.{site}/api/v2/series"),
+            api_key: api_key.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for DatadogSink {
+    fn name(&self) -> &'static str {
+        "Datadog"
+    }
+
+    /// Submit the series of the iteration
+    async fn record(&mut self, iteration_res: &IterationResult) -> Result<()> {
+        let res = self
+            .client
+            .post(&self.series_url)
+            .header("DD-API-KEY", &self.api_key)
+            .json(&json!({ "series": series(iteration_res) }))
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status());
+        if let Err(e) = res {
+            error!("Failed to submit metrics to Datadog: {e}");
+        }
+        Ok(())
+    }
+}
+
+/// One `sdk_pulse.payment.success` point (1 or 0) per scenario that ran, and one
+/// `sdk_pulse.payment.duration` point in seconds per one with a duration, tagged by scenario and host
+fn series(iteration_res: &IterationResult) -> Vec<Value> {
+    let point = |metric: &str, value: f64, scenario: &str| {
+        json!({
+            "metric": metric,
+            "type": GAUGE,
+            "points": [{ "timestamp": iteration_res.start_ts, "value": value }],
+            "tags": [
+                format!("scenario:{scenario}"),
+                format!("host:{}", iteration_res.metadata.host),
+            ],
+        })
+    };
+
+    let mut series = vec![];
+    for scenario in &iteration_res.scenarios {
+        let res = &scenario.measurement;
+        if res.is_skipped() {
+            continue;
+        }
+        let success = if res.is_ok() { 1.0 } else { 0.0 };
+        series.push(point("sdk_pulse.payment.success", success, &scenario.name));
+        if let Some(duration) = res.duration {
+            series.push(point(
+                "sdk_pulse.payment.duration",
+                duration.as_secs_f64(),
+                &scenario.name,
+            ));
+        }
+    }
+    series
+}
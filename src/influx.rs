@@ -0,0 +1,89 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use log::error;
+
+use crate::sinks::Sink;
+use crate::IterationResult;
+
+/// Writes the scenario results to an InfluxDB v2 bucket, in the line protocol
+pub(crate) struct InfluxSink {
+    client: reqwest::Client,
+    write_url: String,
+    org: String,
+    bucket: String,
+    token: Option<String>,
+}
+
+impl InfluxSink {
+    pub(crate) fn new(url: &str, org: &str, bucket: &str, token: Option<&str>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            write_url: format!("{}/api/v2/write", url.trim_end_matches('/')),
+            org: org.into(),
+            bucket: bucket.into(),
+            token: token.map(Into::into),
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for InfluxSink {
+    fn name(&self) -> &'static str {
+        "InfluxDB"
+    }
+
+    /// Write one line per scenario of the iteration, in a single request
+    async fn record(&mut self, iteration_res: &IterationResult) -> Result<()> {
+        let mut req = self
+            .client
+            .post(&self.write_url)
+            .query(&[
+                ("org", self.org.as_str()),
+                ("bucket", self.bucket.as_str()),
+                ("precision", "ns"),
+            ])
+            .body(lines(iteration_res));
+        if let Some(token) = &self.token {
+            req = req.header("Authorization", format!("Token {token}"));
+        }
+
+        if let Err(e) = req.send().await.and_then(|resp| resp.error_for_status()) {
+            error!("Failed to write to InfluxDB: {e}");
+        }
+        Ok(())
+    }
+}
+
+/// Format the scenario results as line protocol, e.g.
+/// `payment,scenario=gl2gl,status=ok success=true,duration_ms=1234i 1700000000000000000`
+fn lines(iteration_res: &IterationResult) -> String {
+    let ts_ns = iteration_res.start_ts as u128 * 1_000_000_000;
+    iteration_res
+        .scenarios
+        .iter()
+        .map(|scenario| {
+            let res = &scenario.measurement;
+            let status = res.outcome.as_str();
+            let mut fields = vec![format!("success={}", res.is_ok())];
+            if let Some(duration_ms) = res.duration_ms() {
+                fields.push(format!("duration_ms={duration_ms}i"));
+            }
+            if let Some(fee_msat) = res.fee_msat {
+                fields.push(format!("fee_msat={fee_msat}i"));
+            }
+            format!(
+                "payment,scenario={},status={status} {} {ts_ns}\n",
+                escape_tag(&scenario.name),
+                fields.join(",")
+            )
+        })
+        .collect()
+}
+
+/// Escape the characters with a special meaning in line protocol tag values
+fn escape_tag(value: &str) -> String {
+    value
+        .replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
@@ -0,0 +1,48 @@
+use std::fs::File;
+use std::io::Write;
+use std::str::FromStr;
+
+use anyhow::Result;
+use env_logger::{Builder, Target};
+use log::{LevelFilter, Log};
+use serde::Deserialize;
+
+/// Format of the app's own log lines
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LogFormat {
+    #[default]
+    Text,
+    /// One JSON object per line, with `ts`, `level`, `target` and `message` fields
+    Json,
+}
+
+/// Build the logger for the app's own logs, to be passed to `BreezServices::init_logging`.
+///
+/// `level` is a [LevelFilter] name like `info` or `debug`, and defaults to `info`. The logs are
+/// written to `file` if set, or else to stderr.
+pub(crate) fn app_logger(
+    level: Option<&str>,
+    format: LogFormat,
+    file: Option<File>,
+) -> Result<Box<dyn Log>> {
+    let level = LevelFilter::from_str(level.unwrap_or("info"))?;
+
+    let mut builder = Builder::new();
+    builder.filter_level(level);
+    if let Some(file) = file {
+        builder.target(Target::Pipe(Box::new(file)));
+    }
+    if format == LogFormat::Json {
+        builder.format(|buf, record| {
+            let line = serde_json::json!({
+                "ts": buf.timestamp_millis().to_string(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            });
+            writeln!(buf, "{line}")
+        });
+    }
+    Ok(Box::new(builder.build()))
+}
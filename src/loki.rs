@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use log::{error, Log, Metadata, Record};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::sinks::Sink;
+use crate::IterationResult;
+
+/// How many lines are pushed to Loki at most in one request
+const LOKI_BATCH_LEN: usize = 500;
+/// How often the lines collected so far are pushed to Loki
+const LOKI_BATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Targets whose logs are not shipped, because pushing to Loki logs with them too
+const UNSHIPPED_TARGETS: [&str; 4] = ["sdk_pulse::loki", "reqwest", "hyper", "h2"];
+
+/// One log line, labeled with the `kind` of stream it belongs to
+struct Entry {
+    kind: &'static str,
+    ts_ns: u128,
+    line: String,
+}
+
+enum Message {
+    Entry(Entry),
+    /// Push everything collected so far, then signal the sender
+    Flush(oneshot::Sender<()>),
+}
+
+/// Ships the app logs and the iteration results to Grafana Loki, in batches.
+///
+/// The lines are collected on a background task and pushed every [LOKI_BATCH_INTERVAL], or as
+/// soon as [LOKI_BATCH_LEN] lines are waiting. The logs go to the stream with the `kind="log"`
+/// label and the results to the one with `kind="result"`, both also with the configured labels.
+#[derive(Clone)]
+pub(crate) struct Loki {
+    messages: mpsc::UnboundedSender<Message>,
+}
+
+impl Loki {
+    pub(crate) fn start(url: &str, labels: HashMap<String, String>) -> Self {
+        let (messages_tx, messages_rx) = mpsc::unbounded_channel();
+        let push_url = format!("{}/loki/api/v1/push", url.trim_end_matches('/'));
+        tokio::spawn(ship(push_url, labels, messages_rx));
+        Self {
+            messages: messages_tx,
+        }
+    }
+
+    /// Wrap the app logger, so everything it logs is also shipped to Loki as one JSON object per
+    /// line, with `level`, `target` and `message` fields
+    pub(crate) fn logger(&self, inner: Box<dyn Log>) -> Box<dyn Log> {
+        Box::new(LokiLogger {
+            inner,
+            loki: self.clone(),
+        })
+    }
+
+    /// Push the lines collected so far, waiting until that's done. The background task is aborted
+    /// when the runtime shuts down, so this must be called before exiting.
+    pub(crate) async fn flush(&self) {
+        let (done_tx, done_rx) = oneshot::channel();
+        if self.messages.send(Message::Flush(done_tx)).is_ok() {
+            // Only fails if the background task stopped
+            let _ = done_rx.await;
+        }
+    }
+
+    fn send(&self, kind: &'static str, line: String) {
+        let ts_ns = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|ts| ts.as_nanos())
+            .unwrap_or_default();
+        // Only fails if the background task stopped, after which nothing can be shipped anyway
+        let _ = self
+            .messages
+            .send(Message::Entry(Entry { kind, ts_ns, line }));
+    }
+}
+
+struct LokiLogger {
+    inner: Box<dyn Log>,
+    loki: Loki,
+}
+
+impl Log for LokiLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        self.inner.log(record);
+        if !self.inner.enabled(record.metadata())
+            || UNSHIPPED_TARGETS
+                .iter()
+                .any(|target| record.target().starts_with(target))
+        {
+            return;
+        }
+        let line = serde_json::json!({
+            "level": record.level().as_str(),
+            "target": record.target(),
+            "message": record.args().to_string(),
+        });
+        self.loki.send("log", line.to_string());
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Ships every iteration result as JSON to Loki, through the same batches as the logs
+pub(crate) struct LokiSink {
+    loki: Loki,
+}
+
+impl LokiSink {
+    pub(crate) fn new(loki: &Loki) -> Self {
+        Self { loki: loki.clone() }
+    }
+}
+
+#[async_trait]
+impl Sink for LokiSink {
+    fn name(&self) -> &'static str {
+        "Loki"
+    }
+
+    async fn record(&mut self, iteration_res: &IterationResult) -> Result<()> {
+        self.loki
+            .send("result", serde_json::to_string(iteration_res)?);
+        Ok(())
+    }
+
+    /// Push the results and logs collected so far
+    async fn flush(&mut self) -> Result<()> {
+        self.loki.flush().await;
+        Ok(())
+    }
+}
+
+/// Collect the lines and push them in batches, until all senders are dropped
+async fn ship(
+    push_url: String,
+    labels: HashMap<String, String>,
+    mut messages: mpsc::UnboundedReceiver<Message>,
+) {
+    let client = reqwest::Client::new();
+    let mut batch = vec![];
+    let mut interval = tokio::time::interval(LOKI_BATCH_INTERVAL);
+    loop {
+        tokio::select! {
+            msg = messages.recv() => match msg {
+                Some(Message::Entry(entry)) => {
+                    batch.push(entry);
+                    if batch.len() >= LOKI_BATCH_LEN {
+                        push(&client, &push_url, &labels, &mut batch).await;
+                    }
+                }
+                Some(Message::Flush(done)) => {
+                    push(&client, &push_url, &labels, &mut batch).await;
+                    let _ = done.send(());
+                }
+                None => {
+                    push(&client, &push_url, &labels, &mut batch).await;
+                    return;
+                }
+            },
+            _ = interval.tick() => push(&client, &push_url, &labels, &mut batch).await,
+        }
+    }
+}
+
+/// Push the batch to Loki, one stream per kind of line. A batch that fails is dropped rather than
+/// retried, so lines don't pile up while Loki is down.
+async fn push(
+    client: &reqwest::Client,
+    push_url: &str,
+    labels: &HashMap<String, String>,
+    batch: &mut Vec<Entry>,
+) {
+    if batch.is_empty() {
+        return;
+    }
+
+    let mut streams: Vec<(&'static str, Vec<[String; 2]>)> = vec![];
+    for entry in batch.drain(..) {
+        let value = [entry.ts_ns.to_string(), entry.line];
+        match streams.iter_mut().find(|(kind, _)| *kind == entry.kind) {
+            Some((_, values)) => values.push(value),
+            None => streams.push((entry.kind, vec![value])),
+        }
+    }
+    let streams: Vec<_> = streams
+        .into_iter()
+        .map(|(kind, values)| {
+            let mut stream_labels = labels.clone();
+            stream_labels.insert("kind".into(), kind.into());
+            serde_json::json!({ "stream": stream_labels, "values": values })
+        })
+        .collect();
+
+    let res = client
+        .post(push_url)
+        .json(&serde_json::json!({ "streams": streams }))
+        .send()
+        .await
+        .and_then(|res| res.error_for_status());
+    if let Err(e) = res {
+        error!("Failed to push to Loki: {e}");
+    }
+}
@@ -1,222 +1,2121 @@
-use std::fs::OpenOptions;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::ops::Deref;
 use std::str::FromStr;
 use std::sync::Arc;
-use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use bip39::{Language, Mnemonic};
 use breez_sdk_core::*;
-use figment::providers::{Format, Toml};
+use chrono::{DateTime, SecondsFormat};
+use clap::{Parser, Subcommand, ValueEnum};
+use figment::providers::{Env, Format, Toml};
 use figment::Figment;
-use log::{error, info};
-use serde::Deserialize;
+use futures::future::join_all;
+use log::{debug, error, info, warn};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, watch, Mutex, Semaphore};
+use tokio_util::sync::CancellationToken;
 
-struct AppEventListener {}
+use crate::alerts::{AlertChannel, Alerts, Telegram, Webhook};
+use crate::backoff::backoff_delay;
+use crate::baseline::Baseline;
+use crate::checkpoint::Checkpoint;
+use crate::datadog::{DatadogSink, DEFAULT_DATADOG_SITE};
+use crate::influx::InfluxSink;
+use crate::logging::LogFormat;
+use crate::loki::{Loki, LokiSink};
+use crate::metrics::{Metrics, MetricsServer};
+use crate::mqtt::MqttSink;
+use crate::nats::NatsSink;
+#[cfg(feature = "otlp")]
+use crate::otlp::OtlpSink;
+use crate::reconcile::Reconciliation;
+use crate::scenarios::{
+    input_type_name, Aggregation, FailureKind, Gl2Gl, Gl2Keysend, Gl2LnAddress, Gl2Onchain,
+    InvoiceOptions, LnUrlAuth, LnUrlWithdraw2Gl, Measurement, RunContext, Scenario, ScenarioResult,
+    SelfPayment, SwapIn,
+};
+use crate::sdk_client::SdkClient;
+use crate::sinks::{check_csv_columns, CsvSink, JsonlSink, Sink, SinkWriter};
+use crate::sqlite::SqliteSink;
+use crate::stats::{Stats, DEFAULT_LATENCY_BUCKETS_SECS};
+use crate::tui::Dashboard;
+
+mod alerts;
+mod backoff;
+mod baseline;
+mod checkpoint;
+mod datadog;
+mod influx;
+mod logging;
+mod loki;
+mod metrics;
+mod mqtt;
+mod nats;
+#[cfg(feature = "otlp")]
+mod otlp;
+mod reconcile;
+mod report;
+mod scenarios;
+mod sdk_client;
+mod sinks;
+mod sqlite;
+mod stats;
+mod tui;
+
+/// Logs the SDK events and forwards them, so scenarios can wait for them
+struct AppEventListener {
+    events: mpsc::UnboundedSender<BreezEvent>,
+}
 impl EventListener for AppEventListener {
     fn on_event(&self, e: BreezEvent) {
         info!("Received Breez event: {e:?}");
+        // Only fails if the receiver is gone, in which case nobody waits for events anymore
+        let _ = self.events.send(e);
     }
 }
 
-/// On first run (if you don't already have a node), set the `invite_code` and leave `mnemonic` as None.
+/// Read the seed of the node in `working_dir`, creating the dir if it doesn't exist.
 ///
-/// On subsequent runs, or if you already have a node, set the `mnemonic`. The `invite_code` can be left empty.
-async fn get_sdk(
-    breez_sdk_api_key: &str,
-    working_dir: &str,
-    invite_code: Option<&str>,
-    mnemonic: Option<&str>,
-) -> Result<Arc<BreezServices>> {
-    let mnemonic_obj = match mnemonic {
-        None => {
-            let mnemonic = Mnemonic::generate_in(Language::English, 12)?;
-            println!("Generated mnemonic: {mnemonic}");
-            mnemonic
-        }
+/// On first run (if you don't already have a node), leave `mnemonic` as None. A mnemonic is then
+/// generated and saved in the working dir, and read from there on subsequent runs.
+///
+/// If you already have a node, set its `mnemonic`, along with the BIP39 `passphrase` its seed was
+/// derived with, if any. The mnemonic can also be a `file:<path>` reference to a file holding it.
+fn load_seed(working_dir: &str, mnemonic: Option<&str>, passphrase: &str) -> Result<[u8; 64]> {
+    // Create working dir if it doesn't exist
+    std::fs::create_dir_all(working_dir)?;
+
+    let mnemonic = mnemonic.map(resolve_mnemonic).transpose()?;
+    let mnemonic_path = format!("{working_dir}/{MNEMONIC_FILE_NAME}");
+    let mnemonic_obj = match mnemonic.as_deref().filter(|m| !m.trim().is_empty()) {
         Some(mnemonic_str) => Mnemonic::from_str(mnemonic_str)?,
+        None => match std::fs::read_to_string(&mnemonic_path) {
+            Ok(mnemonic_str) => {
+                info!("Using mnemonic from {mnemonic_path}");
+                Mnemonic::from_str(mnemonic_str.trim())?
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let mnemonic = Mnemonic::generate_in(Language::English, 12)?;
+                println!("Generated mnemonic: {mnemonic}");
+                save_mnemonic(&mnemonic_path, &mnemonic)?;
+                info!("Saved generated mnemonic to {mnemonic_path}");
+                mnemonic
+            }
+            Err(e) => return Err(e.into()),
+        },
     };
 
-    let seed = mnemonic_obj.to_seed("");
+    Ok(mnemonic_obj.to_seed(passphrase))
+}
+
+/// The configured mnemonic, read from the file if it's a `file:<path>` reference
+fn resolve_mnemonic(mnemonic: &str) -> Result<String> {
+    match mnemonic.strip_prefix("file:") {
+        Some(path) => std::fs::read_to_string(path)
+            .map(|mnemonic| mnemonic.trim().to_string())
+            .map_err(|e| anyhow!("Failed to read the mnemonic from {path}: {e}")),
+        None => Ok(mnemonic.into()),
+    }
+}
 
+/// Connect the SDK of the node with this `seed`.
+///
+/// On first run (if you don't already have a node), set the `invite_code` or the `partner_credentials`
+/// of the `greenlight_config`. On subsequent runs, or if you already have a node, they can be left empty.
+///
+/// The events of the SDK are sent to `events`.
+///
+/// Returns the connected SDK along with how long [BreezServices::connect] took.
+async fn get_sdk(
+    environment: EnvironmentType,
+    breez_sdk_api_key: &str,
+    working_dir: &str,
+    greenlight_config: GreenlightNodeConfig,
+    seed: Vec<u8>,
+    restore_only: Option<bool>,
+    events: mpsc::UnboundedSender<BreezEvent>,
+) -> Result<(Arc<BreezServices>, Duration)> {
     let mut config = BreezServices::default_config(
-        EnvironmentType::Production,
+        environment,
         breez_sdk_api_key.into(),
         breez_sdk_core::NodeConfig::Greenlight {
-            config: GreenlightNodeConfig {
-                partner_credentials: None,
-                invite_code: invite_code.map(Into::into),
-            },
+            config: greenlight_config,
         },
     );
     config.working_dir = working_dir.into();
 
-    // Create working dir if it doesn't exist
-    std::fs::create_dir_all(working_dir)?;
-
+    let connect_start = Instant::now();
     let sdk = BreezServices::connect(
         ConnectRequest {
             config,
-            seed: seed.to_vec(),
-            restore_only: None,
+            seed,
+            restore_only,
         },
-        Box::new(AppEventListener {}),
+        Box::new(AppEventListener { events }),
     )
     .await?;
+    let connect_time = connect_start.elapsed();
+    info!("[{working_dir}] Connected in {connect_time:?}");
+
+    Ok((sdk, connect_time))
+}
+
+/// Everything needed to connect one of the SDKs, and to reconnect it later
+struct SdkConnector<'a> {
+    name: &'static str,
+    environment: EnvironmentType,
+    api_key: &'a str,
+    working_dir: &'a str,
+    partner_credentials: Option<GreenlightCredentials>,
+    mnemonic: Option<&'a str>,
+    mnemonic_passphrase: &'a str,
+    restore_only: Option<bool>,
+    events: mpsc::UnboundedSender<BreezEvent>,
+}
+
+impl SdkConnector<'_> {
+    /// Connect the SDK, returning it already wrapped in its guard along with the connect time
+    async fn connect(&self) -> Result<(SdkGuard, Duration)> {
+        let seed = load_seed(self.working_dir, self.mnemonic, self.mnemonic_passphrase)?;
+        let (sdk, connect_time) = get_sdk(
+            self.environment.clone(),
+            self.api_key,
+            self.working_dir,
+            GreenlightNodeConfig {
+                partner_credentials: self.partner_credentials.clone(),
+                invite_code: None,
+            },
+            seed.to_vec(),
+            self.restore_only,
+            self.events.clone(),
+        )
+        .await?;
+        Ok((SdkGuard::new(sdk, self.name), connect_time))
+    }
+
+    /// Check that the SDK can still reach its node and reconnect it if not, with a backoff
+    /// between attempts.
+    ///
+    /// Returns whether the SDK was reconnected, or an error if all reconnect attempts failed. The
+    /// attempts are bounded per call, so the next call starts over with the old, broken SDK.
+    async fn ensure_connected(&self, sdk: &mut SdkGuard) -> Result<bool> {
+        let Err(e) = sdk.sync().await else {
+            return Ok(false);
+        };
+        error!("[{}] Sync failed, reconnecting: {e}", self.name);
 
-    Ok(sdk)
+        // Close the old session first, so it doesn't conflict with the new one. It's most likely
+        // already gone, so a failure here is expected.
+        if let Err(e) = sdk.sdk.disconnect().await {
+            debug!("[{}] Failed to disconnect the old SDK: {e}", self.name);
+        }
+        sdk.disconnected = true;
+
+        for attempt in 0..RECONNECT_ATTEMPTS {
+            if attempt > 0 {
+                let delay = backoff_delay(attempt - 1, RECONNECT_BASE_DELAY, RECONNECT_MAX_DELAY);
+                info!("[{}] Reconnecting in {delay:?}", self.name);
+                tokio::time::sleep(delay).await;
+            }
+
+            match self.connect().await {
+                Ok((new_sdk, connect_time)) => {
+                    info!("[{}] Reconnected in {connect_time:?}", self.name);
+                    *sdk = new_sdk;
+                    return Ok(true);
+                }
+                Err(e) => error!(
+                    "[{}] Reconnect attempt {}/{RECONNECT_ATTEMPTS} failed: {e}",
+                    self.name,
+                    attempt + 1
+                ),
+            }
+        }
+        Err(anyhow!(
+            "[{}] Failed to reconnect after {RECONNECT_ATTEMPTS} attempts",
+            self.name
+        ))
+    }
+}
+
+/// Disconnects the SDK when dropped, unless [SdkGuard::disconnect] was already called.
+///
+/// This ensures the node session is closed even if `main()` returns early with an error or panics.
+struct SdkGuard<S: SdkClient = BreezServices> {
+    sdk: Arc<S>,
+    name: &'static str,
+    disconnected: bool,
 }
 
+impl<S: SdkClient> SdkGuard<S> {
+    fn new(sdk: Arc<S>, name: &'static str) -> Self {
+        Self {
+            sdk,
+            name,
+            disconnected: false,
+        }
+    }
+
+    async fn disconnect(mut self) -> Result<()> {
+        self.disconnected = true;
+        self.sdk.disconnect().await?;
+        Ok(())
+    }
+}
+
+impl<S: SdkClient> Deref for SdkGuard<S> {
+    type Target = Arc<S>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.sdk
+    }
+}
+
+impl<S: SdkClient> Drop for SdkGuard<S> {
+    fn drop(&mut self) {
+        if self.disconnected {
+            return;
+        }
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            error!("[{}] No runtime left to disconnect the SDK", self.name);
+            return;
+        };
+
+        info!("[{}] Disconnecting the SDK", self.name);
+        let sdk = self.sdk.clone();
+        tokio::task::block_in_place(|| {
+            if let Err(e) = handle.block_on(sdk.disconnect()) {
+                error!("[{}] Failed to disconnect the SDK: {e}", self.name);
+            }
+        });
+    }
+}
+
+/// Write the mnemonic to a new file, readable only by the current user
+fn save_mnemonic(path: &str, mnemonic: &Mnemonic) -> Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(path)?;
+    writeln!(file, "{mnemonic}")?;
+    Ok(())
+}
+
+/// Periodically measures Lightning payment latencies using the Breez SDK
+#[derive(Debug, Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Path to the TOML config file
+    #[arg(long, default_value = "pulse-config.toml")]
+    config: String,
+
+    /// Only connect both nodes and check that the LN Addresses resolve, without paying anything
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Don't print the summary table to stdout at the end of the run
+    #[arg(long)]
+    quiet: bool,
+
+    /// Show a live dashboard of the results instead of the logs, which are then written to
+    /// `app.log` in the SDK logs dir. Press `q` to quit.
+    #[arg(long)]
+    tui: bool,
+
+    /// Start a run bounded by `max_iterations` from scratch, instead of resuming from the
+    /// checkpoint of an interrupted one
+    #[arg(long)]
+    fresh: bool,
+
+    /// Run a single iteration and exit, even if `loop_interval_secs` is configured
+    #[arg(long)]
+    once: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Commands other than running the scenarios, which is done if none is given
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Connect both nodes, print their balances and exit, without paying anything
+    Balance,
+    /// Register a new node with a generated mnemonic, which is saved in its working dir, and exit
+    NewNode {
+        /// Greenlight invite code to register the node with, unless partner credentials are
+        /// configured
+        #[arg(long)]
+        invite_code: Option<String>,
+        /// Working dir of the new node, which must not contain a mnemonic yet
+        #[arg(long)]
+        working_dir: String,
+    },
+    /// Send the spendable balance of a node to an invoice or on-chain address and exit, to
+    /// decommission the node
+    Sweep {
+        /// Node to sweep
+        #[arg(long, value_enum)]
+        node: Node,
+        /// BOLT11 invoice without an amount, or on-chain address to send to through a reverse swap
+        #[arg(long)]
+        to: String,
+        /// Actually send the funds, instead of only logging how much would be sent
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Cooperatively close all channels of a node with the LSP and exit, to decommission the node
+    CloseChannels {
+        /// Node whose channels to close
+        #[arg(long, value_enum)]
+        node: Node,
+        /// Confirm closing the channels, which is required
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+/// One of the two configured nodes
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Node {
+    #[value(name = "sdk_1")]
+    Sdk1,
+    #[value(name = "sdk_2")]
+    Sdk2,
+}
+
+/// Config read from the TOML file.
+///
+/// Every field can be overridden by an environment variable named after the field in uppercase,
+/// prefixed with `PULSE_`. For example `PULSE_BREEZ_API_KEY`, `PULSE_SDK_1_MNEMONIC` or
+/// `PULSE_SDK_2_MNEMONIC`.
 #[derive(Debug, PartialEq, Deserialize)]
 struct PulseConfig {
     breez_api_key: String,
-    sdk_1_mnemonic: String,
-    sdk_2_mnemonic: String,
+    /// Mnemonics of the two nodes, or `file:<path>` references to files holding them. If not set or
+    /// empty, the mnemonic saved in the node's working dir is used, or a new one is generated and
+    /// saved there on first run.
+    sdk_1_mnemonic: Option<String>,
+    sdk_2_mnemonic: Option<String>,
+    /// Optional BIP39 passphrase the seeds of both nodes are derived with, unless overridden for
+    /// one of them by `sdk_1_mnemonic_passphrase` or `sdk_2_mnemonic_passphrase`. Defaults to none.
+    mnemonic_passphrase: Option<String>,
+    sdk_1_mnemonic_passphrase: Option<String>,
+    sdk_2_mnemonic_passphrase: Option<String>,
+    /// If true, only connect to existing nodes and fail instead of registering new ones
+    restore_only: Option<bool>,
+    /// Optional dir holding the working dirs of the nodes, `sdk-1` and `sdk-2`, created if missing.
+    /// If not set, they are `working-dir-sdk-1` and `working-dir-sdk-2` in the current dir.
+    working_dir_root: Option<String>,
+    /// Breez environment to connect to, `production` or `staging`. Defaults to `production`.
+    environment: Option<String>,
+    /// Optional paths to a Greenlight partner certificate and key, to register new nodes with
+    /// instead of an invite code. Both must be set.
+    partner_cert_path: Option<String>,
+    partner_key_path: Option<String>,
 
-    /// Relative or absolute path to the CSV file with iteration measurements
-    iterations_csv_full_path: String,
+    /// Optional minimum channels balance of the sender (sdk_1). Below it, the scenarios that pay
+    /// from sdk_1 are skipped, so a drained node doesn't show up as an outage.
+    min_sender_balance_msat: Option<u64>,
+    /// If true, create a swap-in address on sdk_1 whenever its balance is below
+    /// `min_sender_balance_msat`, to which funds can be sent to top it up
+    auto_topup_swapin: Option<bool>,
+
+    /// Optional relative or absolute path to the CSV file with iteration measurements. Date
+    /// placeholders like `%Y-%m-%d` are replaced with the date of each iteration, to start a new
+    /// file every day.
+    iterations_csv_full_path: Option<String>,
+    /// Optional names of the CSV columns to write, in this order, like `["start_ts", "gl2gl_ms"]`.
+    /// The run fails to start if one isn't known. Defaults to all columns.
+    csv_columns: Option<Vec<String>>,
     /// Relative or absolute to where the iteration logs folders will be placed
     iterations_logs_dir_path: String,
+    /// Level of the app's own logs, like `info` or `debug`. Defaults to `info`.
+    ///
+    /// The SDK logs are always written to `iterations_logs_dir_path`.
+    log_level: Option<String>,
+    /// Format of the app's own logs. Defaults to [LogFormat::Text].
+    log_format: Option<LogFormat>,
+    /// Optional relative or absolute path to a JSON Lines file with iteration measurements
+    jsonl_full_path: Option<String>,
+    /// Optional relative or absolute path to a SQLite database with iteration measurements
+    sqlite_path: Option<String>,
+    /// Optional address (e.g. `127.0.0.1:9090`) on which to serve Prometheus metrics, updated as
+    /// soon as each scenario finished (except in warm-up iterations)
+    metrics_bind_addr: Option<String>,
+    /// Optional ascending upper bounds of the latency buckets, in seconds, used in the summary and
+    /// for the Prometheus histogram. A `+Inf` bucket is always added. Defaults to
+    /// [DEFAULT_LATENCY_BUCKETS_SECS].
+    latency_buckets_secs: Option<Vec<f64>>,
+    /// Optional URL to which a JSON POST is sent whenever a scenario fails, at most once per
+    /// minute per scenario
+    failure_webhook_url: Option<String>,
+    /// Optional Telegram bot token and chat ID to which a message is sent whenever a scenario
+    /// fails, like the `failure_webhook_url`
+    telegram_bot_token: Option<String>,
+    telegram_chat_id: Option<String>,
+    /// Only alert when a scenario failed this many times in a row. Defaults to 1, alerting on every
+    /// failure.
+    alert_after_consecutive_failures: Option<u64>,
+    /// Optional InfluxDB v2 base URL (e.g. `http://localhost:8086`) to write the results to.
+    /// If set, `influx_org` and `influx_bucket` are required too.
+    influx_url: Option<String>,
+    influx_org: Option<String>,
+    influx_bucket: Option<String>,
+    influx_token: Option<String>,
+    /// Optional Datadog API key, to submit the payment durations and outcomes to Datadog after
+    /// every iteration, on the `datadog_site` (defaults to [DEFAULT_DATADOG_SITE])
+    datadog_api_key: Option<String>,
+    datadog_site: Option<String>,
+    /// Optional Grafana Loki URL, to also ship the app logs and the iteration results to, with
+    /// the `app="sdk_pulse"` label and `loki_labels`
+    loki_url: Option<String>,
+    loki_labels: Option<HashMap<String, String>>,
+    /// Optional NATS server URL and subject, to publish every iteration result as JSON to
+    nats_url: Option<String>,
+    nats_subject: Option<String>,
+    /// Optional MQTT broker `host` or `host:port`, with an IPv6 host in brackets like `[::1]:1883`,
+    /// to publish the duration and status of every scenario to as retained messages under `mqtt_topic` (defaults to [DEFAULT_MQTT_TOPIC]),
+    /// optionally authenticating with `mqtt_username` and `mqtt_password`
+    mqtt_broker: Option<String>,
+    mqtt_topic: Option<String>,
+    mqtt_username: Option<String>,
+    mqtt_password: Option<String>,
+    /// Optional OTLP gRPC endpoint of an OpenTelemetry collector, like `http://localhost:4317`, to
+    /// which the payment durations and outcomes are exported after every iteration. Requires the
+    /// `otlp` feature.
+    otlp_endpoint: Option<String>,
+    /// If set, run the scenarios in a loop, pausing this many seconds between iterations.
+    /// If not set, the scenarios are run only once.
+    loop_interval_secs: Option<u64>,
+    /// Randomly shift each pause between iterations by up to this many seconds, earlier or later,
+    /// so multiple instances don't stay in sync. Defaults to 0.
+    loop_jitter_secs: Option<u64>,
+    /// If true, run the scenarios of an iteration concurrently, except for `gl2gl` which needs both
+    /// nodes and runs after the others. Concurrent payments may affect each other's latency.
+    parallel_scenarios: Option<bool>,
+    /// Optional pause between two scenarios run one after the other, to not trip rate limits with
+    /// back-to-back payments. Defaults to 0.
+    inter_scenario_delay_ms: Option<u64>,
+    /// How many scenarios issuing a payment may run at once with `parallel_scenarios`, to not
+    /// overwhelm the nodes or the LSP. Defaults to no limit with `parallel_scenarios`, otherwise
+    /// to 1.
+    max_concurrent_payments: Option<usize>,
+    /// Optional names of the scenarios to run, like `["gl2gl", "gl2wos"]`. The others are still
+    /// listed in the outputs, but marked as skipped. If not set, all configured scenarios run.
+    enabled_scenarios: Option<Vec<String>>,
+    /// Number of iterations to run first in loop mode, without recording their results in the
+    /// outputs and stats. Defaults to 0.
+    warmup_iterations: Option<u64>,
+    /// Maximum number of recorded iterations in loop mode, not counting the warm-up iterations.
+    /// If not set, the loop runs until the process is stopped.
+    max_iterations: Option<u64>,
+    /// Maximum duration of a single scenario, after which it is marked as failed.
+    /// Defaults to [DEFAULT_SCENARIO_TIMEOUT_SECS].
+    scenario_timeout_secs: Option<u64>,
+    /// How long to wait for the nodes to be synced before an iteration, after which the
+    /// iteration is skipped. Defaults to [DEFAULT_SYNC_TIMEOUT_SECS].
+    sync_timeout_secs: Option<u64>,
+    /// How many times a failed scenario is attempted in total. Defaults to 1 (no retries).
+    retry_attempts: Option<u32>,
+    /// Optional time budget of the scenarios of an iteration, counted from when the first one
+    /// starts. Once an attempt started now could time out after it, no further retries or
+    /// scenarios are started, and the remaining scenarios are marked as skipped. Must be longer
+    /// than `scenario_timeout_secs`. If not set, iterations take as long as their scenarios.
+    iteration_deadline_secs: Option<u64>,
+    /// Optional name of a scenario to run once right after connecting, before the first iteration,
+    /// to measure the first payment of a fresh connection. Its duration is recorded with every
+    /// iteration as `cold_start_payment_ms`, but not counted in the stats.
+    cold_start_scenario: Option<String>,
+    /// How many times each scenario runs per iteration, to reduce the variance of a single sample.
+    /// Defaults to 1.
+    repetitions_per_scenario: Option<u32>,
+    /// How the repetitions of a scenario are combined into its result, `median`, `mean` or `all`.
+    /// With `all`, the CSV and the other outputs with one value per scenario still get the median,
+    /// while the JSON outputs list every repetition and the stats count each of them. Defaults to
+    /// `median`.
+    aggregation: Option<Aggregation>,
+    /// Optional path to a TOML file with the expected p90 duration per scenario. If set, the run
+    /// fails when a p90 exceeds its baseline times `regression_factor`.
+    baseline_path: Option<String>,
+    /// How much slower than the baseline a scenario may be. Defaults to
+    /// [DEFAULT_REGRESSION_FACTOR].
+    regression_factor: Option<f64>,
+
+    /// LN Addresses to pay on every run. If not set, `ln_address_wos` and `ln_address_tor_node`
+    /// are used instead.
+    ln_addresses: Option<Vec<LnAddressTarget>>,
+    ln_address_wos: Option<String>,
+    ln_address_tor_node: Option<String>,
+    /// Optional LNURL-withdraw link to receive from. If not set, the LNURL-withdraw test is not run.
+    ln_withdraw_url: Option<String>,
+    /// Optional LNURL-auth link to log in with. If not set, the LNURL-auth test is not run.
+    lnurl_auth_url: Option<String>,
+    /// Optional node ID to send keysend payments to. If not set, the keysend test is not run.
+    keysend_node_id: Option<String>,
+    /// Optional on-chain address to send to through a reverse swap. If not set, the reverse swap
+    /// test is not run.
+    reverse_swap_onchain_address: Option<String>,
+    /// If true, also run the `self` test, where sdk_1 pays its own invoice
+    self_payment: Option<bool>,
+    /// If true, also run the `swapin` test, which creates a swap-in address on sdk_2
+    swapin: Option<bool>,
+
+    /// Optional amount in fiat to pay in all Lightning tests, converted to msat at startup with
+    /// the SDK's exchange rate for `fiat_currency` (e.g. `USD`). If set, it replaces the msat
+    /// amounts below.
+    amount_fiat: Option<f64>,
+    /// Currency of `amount_fiat` and of the BTC exchange rate recorded on every iteration.
+    /// Defaults to [DEFAULT_FIAT_CURRENCY].
+    fiat_currency: Option<String>,
 
-    ln_address_wos: String,
-    ln_address_tor_node: String,
+    /// Amounts of the individual tests. Each defaults to [DEFAULT_AMOUNT_MSAT].
+    ///
+    /// The `gl2wos` and `gl2tor` amounts only apply to `ln_address_wos` and `ln_address_tor_node`.
+    gl2wos_amount_msat: Option<u64>,
+    gl2gl_amount_msat: Option<u64>,
+    gl2tor_amount_msat: Option<u64>,
+    lnurlw2gl_amount_msat: Option<u64>,
+    gl2keysend_amount_msat: Option<u64>,
+    self_amount_msat: Option<u64>,
+    /// Defaults to [DEFAULT_REVERSE_SWAP_AMOUNT_SAT]
+    gl2onchain_amount_sat: Option<u64>,
+
+    /// LNURL-pay comments and invoice descriptions of the individual tests, to tag the payments
+    /// in the wallet history. They default to `test-gl2lnurl` for the LN Address tests and to
+    /// `test-<scenario>` for the others.
+    ///
+    /// The `gl2wos` and `gl2tor` comments only apply to `ln_address_wos` and `ln_address_tor_node`.
+    gl2wos_comment: Option<String>,
+    gl2tor_comment: Option<String>,
+    gl2gl_description: Option<String>,
+    lnurlw2gl_description: Option<String>,
+    self_description: Option<String>,
+
+    /// Optional expiry and min final CLTV expiry delta of the gl2gl and self invoices. The expiry
+    /// defaults to [DEFAULT_INVOICE_EXPIRY_SECS], the CLTV to the SDK default.
+    invoice_expiry_secs: Option<u32>,
+    invoice_cltv: Option<u32>,
+    /// If true, the gl2gl and self invoices are created with the opening fee params the LSP offers
+    /// for their amount, instead of the ones the SDK picks. Either way, the opening fee is recorded.
+    fetch_opening_fee_params: Option<bool>,
+    /// If true, the gl2gl and self invoices only contain the SHA256 hash of `gl2gl_description`
+    /// and `self_description` (LUD-06 style), instead of the description itself, so the payer
+    /// can't show the description.
+    use_description_hash: Option<bool>,
+    /// If sdk_2 lacks the inbound liquidity for `gl2gl_amount_msat`, the gl2gl invoice is created
+    /// with the LSP's opening fee params, so paying it opens a channel. If this is true, gl2gl fails
+    /// with the `insufficient_inbound` status without creating an invoice instead. Defaults to
+    /// false.
+    gl2gl_fail_if_no_inbound: Option<bool>,
+
+    /// If true, LN Address payments with an amount outside of the endpoint's sendable range send
+    /// the nearest accepted amount instead. By default they fail.
+    clamp_ln_address_amount: Option<bool>,
+}
+
+impl PulseConfig {
+    /// The configured LN Addresses, falling back to the WoS and Tor node addresses
+    fn ln_address_targets(&self) -> Vec<LnAddressTarget> {
+        if let Some(ln_addresses) = &self.ln_addresses {
+            return ln_addresses.clone();
+        }
+
+        let legacy_targets = [
+            (
+                "gl2wos",
+                &self.ln_address_wos,
+                self.gl2wos_amount_msat,
+                &self.gl2wos_comment,
+            ),
+            (
+                "gl2tor",
+                &self.ln_address_tor_node,
+                self.gl2tor_amount_msat,
+                &self.gl2tor_comment,
+            ),
+        ];
+        legacy_targets
+            .into_iter()
+            .filter_map(|(name, address, amount_msat, comment)| {
+                address.as_ref().map(|address| LnAddressTarget {
+                    name: name.into(),
+                    address: address.clone(),
+                    amount_msat,
+                    comment: comment.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// The configured channels for alerts about failed scenarios
+    fn alert_channels(&self) -> Vec<Box<dyn AlertChannel>> {
+        let mut channels: Vec<Box<dyn AlertChannel>> = vec![];
+        if let Some(url) = &self.failure_webhook_url {
+            channels.push(Box::new(Webhook::new(url)));
+        }
+        if let (Some(bot_token), Some(chat_id)) = (&self.telegram_bot_token, &self.telegram_chat_id)
+        {
+            channels.push(Box::new(Telegram::new(bot_token, chat_id)));
+        }
+        channels
+    }
+
+    /// The configured outputs for the iteration results
+    fn sinks(
+        &self,
+        scenarios: &[Box<dyn Scenario>],
+        fiat_currency: &str,
+    ) -> Result<Vec<Box<dyn Sink>>> {
+        let mut sinks: Vec<Box<dyn Sink>> = vec![];
+        if let Some(csv_path) = &self.iterations_csv_full_path {
+            sinks.push(Box::new(CsvSink::new(
+                csv_path,
+                scenarios,
+                fiat_currency,
+                self.csv_columns.as_deref(),
+            )?));
+        }
+        if let Some(jsonl_path) = &self.jsonl_full_path {
+            sinks.push(Box::new(JsonlSink::new(jsonl_path)));
+        }
+        if let Some(sqlite_path) = &self.sqlite_path {
+            sinks.push(Box::new(SqliteSink::open(sqlite_path)?));
+        }
+        if let Some(url) = &self.influx_url {
+            let (Some(org), Some(bucket)) = (&self.influx_org, &self.influx_bucket) else {
+                return Err(anyhow!(
+                    "influx_org and influx_bucket are required with influx_url"
+                ));
+            };
+            sinks.push(Box::new(InfluxSink::new(
+                url,
+                org,
+                bucket,
+                self.influx_token.as_deref(),
+            )));
+        }
+        if let Some(api_key) = &self.datadog_api_key {
+            sinks.push(Box::new(DatadogSink::new(
+                api_key,
+                self.datadog_site.as_deref().unwrap_or(DEFAULT_DATADOG_SITE),
+            )));
+        }
+        if let Some(url) = &self.nats_url {
+            let Some(subject) = &self.nats_subject else {
+                return Err(anyhow!("nats_subject is required with nats_url"));
+            };
+            sinks.push(Box::new(NatsSink::new(url, subject)));
+        }
+        if let Some(broker) = &self.mqtt_broker {
+            let credentials = match (&self.mqtt_username, &self.mqtt_password) {
+                (Some(username), Some(password)) => Some((username.as_str(), password.as_str())),
+                (None, None) => None,
+                _ => {
+                    return Err(anyhow!(
+                        "mqtt_username and mqtt_password must be set together"
+                    ))
+                }
+            };
+            sinks.push(Box::new(MqttSink::new(
+                broker,
+                self.mqtt_topic.as_deref().unwrap_or(DEFAULT_MQTT_TOPIC),
+                credentials,
+            )?));
+        }
+        if let Some(endpoint) = &self.otlp_endpoint {
+            #[cfg(feature = "otlp")]
+            sinks.push(Box::new(OtlpSink::new(endpoint)?));
+            #[cfg(not(feature = "otlp"))]
+            return Err(anyhow!(
+                "otlp_endpoint {endpoint} is set, but sdk_pulse was built without the otlp feature"
+            ));
+        }
+        Ok(sinks)
+    }
+
+    /// The configured Breez environment
+    fn environment(&self) -> Result<EnvironmentType> {
+        match self
+            .environment
+            .as_deref()
+            .map(str::to_lowercase)
+            .as_deref()
+        {
+            None | Some("production") => Ok(EnvironmentType::Production),
+            Some("staging") => Ok(EnvironmentType::Staging),
+            Some(other) => Err(anyhow!(
+                "Unknown environment {other:?}, expected \"production\" or \"staging\""
+            )),
+        }
+    }
+
+    /// Labels of the Loki streams, `app="sdk_pulse"` unless overridden by `loki_labels`
+    fn loki_labels(&self) -> HashMap<String, String> {
+        let mut labels = HashMap::from([("app".to_string(), "sdk_pulse".to_string())]);
+        labels.extend(self.loki_labels.clone().unwrap_or_default());
+        labels
+    }
+
+    /// Working dirs of sdk_1 and sdk_2
+    fn working_dirs(&self) -> (String, String) {
+        match &self.working_dir_root {
+            Some(root) => (format!("{root}/sdk-1"), format!("{root}/sdk-2")),
+            None => ("working-dir-sdk-1".into(), "working-dir-sdk-2".into()),
+        }
+    }
+
+    /// The Greenlight partner credentials read from the configured files, if any
+    fn partner_credentials(&self) -> Result<Option<GreenlightCredentials>> {
+        let (cert_path, key_path) = match (&self.partner_cert_path, &self.partner_key_path) {
+            (Some(cert_path), Some(key_path)) => (cert_path, key_path),
+            (None, None) => return Ok(None),
+            _ => {
+                return Err(anyhow!(
+                    "partner_cert_path and partner_key_path must be set together"
+                ))
+            }
+        };
+        let read = |path: &str| {
+            std::fs::read(path)
+                .map_err(|e| anyhow!("Failed to read partner credentials {path}: {e}"))
+        };
+        Ok(Some(GreenlightCredentials {
+            developer_cert: read(cert_path)?,
+            developer_key: read(key_path)?,
+        }))
+    }
+
+    /// The configured latency bucket bounds, checked to be positive and ascending
+    fn latency_buckets_secs(&self) -> Result<Vec<f64>> {
+        let buckets = self
+            .latency_buckets_secs
+            .clone()
+            .unwrap_or_else(|| DEFAULT_LATENCY_BUCKETS_SECS.to_vec());
+        if buckets.is_empty()
+            || buckets[0] <= 0.0
+            || buckets.windows(2).any(|pair| pair[0] >= pair[1])
+        {
+            return Err(anyhow!(
+                "latency_buckets_secs must be positive and strictly ascending, got {buckets:?}"
+            ));
+        }
+        Ok(buckets)
+    }
+
+    /// The configured iteration deadline, checked to leave room for at least one scenario
+    fn iteration_deadline(&self) -> Result<Option<Duration>> {
+        let Some(deadline_secs) = self.iteration_deadline_secs else {
+            return Ok(None);
+        };
+        let timeout_secs = self
+            .scenario_timeout_secs
+            .unwrap_or(DEFAULT_SCENARIO_TIMEOUT_SECS);
+        if deadline_secs <= timeout_secs {
+            return Err(anyhow!(
+                "iteration_deadline_secs must be longer than scenario_timeout_secs ({timeout_secs}), got {deadline_secs}"
+            ));
+        }
+        Ok(Some(Duration::from_secs(deadline_secs)))
+    }
+
+    /// The configured `amount_fiat`, which has to be a positive amount
+    fn amount_fiat(&self) -> Result<Option<f64>> {
+        match self.amount_fiat {
+            Some(amount_fiat) if !amount_fiat.is_finite() || amount_fiat <= 0.0 => Err(anyhow!(
+                "amount_fiat must be a positive amount, got {amount_fiat}"
+            )),
+            amount_fiat => Ok(amount_fiat),
+        }
+    }
+
+    fn max_concurrent_payments(&self) -> usize {
+        match (self.max_concurrent_payments, self.parallel_scenarios) {
+            (Some(max), _) => max,
+            (None, Some(true)) => Semaphore::MAX_PERMITS,
+            (None, _) => 1,
+        }
+    }
+
+    /// Whether the scenario with this name is enabled in `enabled_scenarios`
+    fn is_enabled(&self, scenario_name: &str) -> bool {
+        match &self.enabled_scenarios {
+            Some(enabled) => enabled.iter().any(|name| name == scenario_name),
+            None => true,
+        }
+    }
+
+    /// The scenarios to run on every iteration, based on which targets are configured.
+    ///
+    /// If set, `fiat_amount_msat` is used as amount of all Lightning payments instead of the
+    /// configured msat amounts.
+    fn scenarios(&self, fiat_amount_msat: Option<u64>) -> Vec<Box<dyn Scenario>> {
+        let amount_msat = |configured: Option<u64>| {
+            fiat_amount_msat
+                .or(configured)
+                .unwrap_or(DEFAULT_AMOUNT_MSAT)
+        };
+
+        let mut scenarios: Vec<Box<dyn Scenario>> = vec![];
+        for target in self.ln_address_targets() {
+            scenarios.push(Box::new(Gl2LnAddress {
+                name: target.name,
+                address: target.address,
+                amount_msat: amount_msat(target.amount_msat),
+                comment: target.comment.unwrap_or(DEFAULT_LN_ADDRESS_COMMENT.into()),
+                clamp_amount: self.clamp_ln_address_amount == Some(true),
+            }));
+        }
+        let invoice = InvoiceOptions {
+            expiry_secs: self
+                .invoice_expiry_secs
+                .unwrap_or(DEFAULT_INVOICE_EXPIRY_SECS),
+            cltv: self.invoice_cltv,
+            fetch_opening_fee_params: self.fetch_opening_fee_params == Some(true),
+            use_description_hash: self.use_description_hash == Some(true),
+        };
+        scenarios.push(Box::new(Gl2Gl {
+            amount_msat: amount_msat(self.gl2gl_amount_msat),
+            description: self
+                .gl2gl_description
+                .clone()
+                .unwrap_or("test-gl2gl".into()),
+            invoice,
+            fail_if_no_inbound: self.gl2gl_fail_if_no_inbound == Some(true),
+        }));
+        if let Some(lnurl) = &self.ln_withdraw_url {
+            scenarios.push(Box::new(LnUrlWithdraw2Gl {
+                lnurl: lnurl.clone(),
+                amount_msat: amount_msat(self.lnurlw2gl_amount_msat),
+                description: self
+                    .lnurlw2gl_description
+                    .clone()
+                    .unwrap_or("test-lnurlw2gl".into()),
+            }));
+        }
+        if let Some(lnurl) = &self.lnurl_auth_url {
+            scenarios.push(Box::new(LnUrlAuth {
+                lnurl: lnurl.clone(),
+            }));
+        }
+        if let Some(node_id) = &self.keysend_node_id {
+            scenarios.push(Box::new(Gl2Keysend {
+                node_id: node_id.clone(),
+                amount_msat: amount_msat(self.gl2keysend_amount_msat),
+            }));
+        }
+        if let Some(address) = &self.reverse_swap_onchain_address {
+            scenarios.push(Box::new(Gl2Onchain {
+                address: address.clone(),
+                amount_sat: self
+                    .gl2onchain_amount_sat
+                    .unwrap_or(DEFAULT_REVERSE_SWAP_AMOUNT_SAT),
+            }));
+        }
+        if self.self_payment == Some(true) {
+            scenarios.push(Box::new(SelfPayment {
+                amount_msat: amount_msat(self.self_amount_msat),
+                description: self.self_description.clone().unwrap_or("test-self".into()),
+                invoice,
+            }));
+        }
+        if self.swapin == Some(true) {
+            scenarios.push(Box::new(SwapIn));
+        }
+        scenarios
+    }
+}
+
+/// An LN Address to pay on every run
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+struct LnAddressTarget {
+    /// Name of the target, used as prefix for its CSV columns
+    name: String,
+    address: String,
+    /// Defaults to [DEFAULT_AMOUNT_MSAT]
+    amount_msat: Option<u64>,
+    /// LNURL-pay comment, defaults to [DEFAULT_LN_ADDRESS_COMMENT]
+    comment: Option<String>,
+}
+
+const DEFAULT_SCENARIO_TIMEOUT_SECS: u64 = 120;
+const DEFAULT_AMOUNT_MSAT: u64 = 1_000;
+/// How many times to try reconnecting an SDK which lost its connection, before giving up
+const RECONNECT_ATTEMPTS: u32 = 5;
+
+const DEFAULT_SYNC_TIMEOUT_SECS: u64 = 60;
+/// How many blocks a node may be behind the other one to still count as synced
+const MAX_BLOCKS_BEHIND: u32 = 2;
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(5);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(120);
+const DEFAULT_FIAT_CURRENCY: &str = "USD";
+const DEFAULT_LN_ADDRESS_COMMENT: &str = "test-gl2lnurl";
+/// Small expiry of the gl2gl and self invoices, so VLS can prune older invoices
+const DEFAULT_INVOICE_EXPIRY_SECS: u32 = 60;
+/// Reverse swaps have a much higher minimum amount than Lightning payments
+const DEFAULT_REVERSE_SWAP_AMOUNT_SAT: u64 = 50_000;
+/// Name of the file in a node's working dir holding its generated mnemonic
+const MNEMONIC_FILE_NAME: &str = "mnemonic.txt";
+/// Checkpoint of a run bounded by `max_iterations`, in the current dir
+const CHECKPOINT_FILE_NAME: &str = "state.json";
+/// Prefix of the MQTT topics, if none is configured
+const DEFAULT_MQTT_TOPIC: &str = "sdk_pulse";
+/// Default of `regression_factor`
+const DEFAULT_REGRESSION_FACTOR: f64 = 1.2;
+
+/// Where and by which build the iterations were run, the same for every iteration
+#[derive(Clone, Debug, Serialize)]
+struct RunMetadata {
+    host: String,
+    version: &'static str,
+    git_sha: &'static str,
+}
+
+impl RunMetadata {
+    fn new() -> Self {
+        let host = hostname::get()
+            .map(|host| host.to_string_lossy().into_owned())
+            .unwrap_or_else(|e| {
+                error!("Failed to get the hostname: {e}");
+                "unknown".into()
+            });
+        Self {
+            host,
+            version: env!("CARGO_PKG_VERSION"),
+            git_sha: env!("GIT_SHA"),
+        }
+    }
+}
+
+/// All scenario outcomes of one run, serialized as one JSONL line
+#[derive(Debug, Serialize)]
+struct IterationResult {
+    start_ts: u64,
+    /// `start_ts` as an RFC 3339 UTC timestamp, for humans reading the results
+    iso_ts: String,
+    #[serde(flatten)]
+    metadata: RunMetadata,
+    /// How long connecting each SDK took at startup
+    sdk1_connect_ms: u64,
+    sdk2_connect_ms: u64,
+    /// How long the `cold_start_scenario` took right after connecting, if configured and it
+    /// succeeded
+    cold_start_payment_ms: Option<u64>,
+    /// The configured fiat amount and the msat amount it was converted to, if paying in fiat
+    amount_fiat: Option<f64>,
+    fiat_amount_msat: Option<u64>,
+    /// Whether an SDK had lost its connection and was reconnected before this iteration
+    reconnected: bool,
+    /// Price of 1 BTC in `fiat_currency` at the start of the iteration, if it could be fetched
+    btc_rate: Option<f64>,
+    /// Channels balances of the sender (sdk_1) and receiver (sdk_2) nodes, before and after the
+    /// scenarios ran
+    sender_balance_before_msat: Option<u64>,
+    sender_balance_after_msat: Option<u64>,
+    receiver_balance_before_msat: Option<u64>,
+    receiver_balance_after_msat: Option<u64>,
+    scenarios: Vec<ScenarioResult>,
+}
+
+#[cfg(test)]
+impl IterationResult {
+    /// An iteration at a fixed time with only the given scenario results, the rest left empty
+    fn with_scenarios(scenarios: Vec<ScenarioResult>) -> Self {
+        Self {
+            start_ts: 1_700_000_000,
+            iso_ts: iso_timestamp(1_700_000_000),
+            metadata: RunMetadata {
+                host: "test-host".into(),
+                version: "0.1.0",
+                git_sha: "0000000",
+            },
+            sdk1_connect_ms: 1200,
+            sdk2_connect_ms: 1300,
+            cold_start_payment_ms: None,
+            amount_fiat: None,
+            fiat_amount_msat: None,
+            reconnected: false,
+            btc_rate: None,
+            sender_balance_before_msat: None,
+            sender_balance_after_msat: None,
+            receiver_balance_before_msat: None,
+            receiver_balance_after_msat: None,
+            scenarios,
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
+    // First thing, as until then SIGUSR1 would kill the process
+    let stats_dump_signal = StatsDumpSignal::new();
     let start = SystemTime::now();
     let start_ts = start.duration_since(UNIX_EPOCH)?.as_secs();
 
-    let figment = Figment::new().merge(Toml::file("pulse-config.toml"));
-    let config: PulseConfig = figment.extract()?;
+    let cli = Cli::parse();
+    let figment = Figment::new()
+        .merge(Toml::file(&cli.config))
+        .merge(Env::prefixed("PULSE_"));
+    let mut config: PulseConfig = figment.extract()?;
+    if cli.once {
+        config.loop_interval_secs = None;
+    }
 
     let log_dir = &format!("{}/sdk-log-{start_ts}", config.iterations_logs_dir_path);
     std::fs::create_dir_all(log_dir)?;
-    BreezServices::init_logging(log_dir, None)?;
+    // The logs would garble the dashboard, so write them to a file instead
+    let log_file = cli
+        .tui
+        .then(|| File::create(format!("{log_dir}/app.log")))
+        .transpose()?;
+    let app_logger = logging::app_logger(
+        config.log_level.as_deref(),
+        config.log_format.unwrap_or_default(),
+        log_file,
+    )?;
+    let loki = config
+        .loki_url
+        .as_deref()
+        .map(|url| Loki::start(url, config.loki_labels()));
+    let app_logger = match &loki {
+        Some(loki) => loki.logger(app_logger),
+        None => app_logger,
+    };
+    BreezServices::init_logging(log_dir, Some(app_logger))?;
 
-    let sdk_1 = get_sdk(
-        &config.breez_api_key,
-        "working-dir-sdk-1",
-        None,
-        Some(&config.sdk_1_mnemonic),
-    )
-    .await?;
-    info!("[sdk_1] Node info: {:?}", sdk_1.node_info()?);
+    let res = run(cli, config, loki.as_ref(), stats_dump_signal).await;
+    // Ship what was logged until the very end, before the runtime shutdown aborts the shipping
+    if let Some(loki) = &loki {
+        loki.flush().await;
+    }
+    res
+}
 
-    let sdk_2 = get_sdk(
-        &config.breez_api_key,
-        "working-dir-sdk-2",
-        None,
-        Some(&config.sdk_2_mnemonic),
-    )
-    .await?;
+/// Run the given command, or the scenarios if there is none, once the logging is set up
+async fn run(
+    cli: Cli,
+    config: PulseConfig,
+    loki: Option<&Loki>,
+    stats_dump_signal: StatsDumpSignal,
+) -> Result<()> {
+    if let Some(Command::NewNode {
+        invite_code,
+        working_dir,
+    }) = &cli.command
+    {
+        return new_node(&config, invite_code.as_deref(), working_dir).await;
+    }
+
+    let shutdown = CancellationToken::new();
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            shutdown_signal().await;
+            info!("Received shutdown signal, stopping after the current iteration");
+            shutdown.cancel();
+        }
+    });
+
+    let latency_buckets_secs = config.latency_buckets_secs()?;
+    let iteration_deadline = config.iteration_deadline()?;
+    let amount_fiat = config.amount_fiat()?;
+    let metrics = Arc::new(Metrics::new(&latency_buckets_secs)?);
+    let metrics_server = match &config.metrics_bind_addr {
+        Some(bind_addr) => Some(MetricsServer::start(bind_addr, metrics.clone()).await?),
+        None => None,
+    };
+
+    let (sdk_1_events_tx, sdk_1_events) = mpsc::unbounded_channel();
+    let (sdk_2_events_tx, sdk_2_events) = mpsc::unbounded_channel();
+    let environment = config.environment()?;
+    let partner_credentials = config.partner_credentials()?;
+    let (sdk_1_working_dir, sdk_2_working_dir) = config.working_dirs();
+    let sdk_1_connector = SdkConnector {
+        name: "sdk_1",
+        environment: environment.clone(),
+        api_key: &config.breez_api_key,
+        working_dir: &sdk_1_working_dir,
+        partner_credentials: partner_credentials.clone(),
+        mnemonic: config.sdk_1_mnemonic.as_deref(),
+        mnemonic_passphrase: config
+            .sdk_1_mnemonic_passphrase
+            .as_ref()
+            .or(config.mnemonic_passphrase.as_ref())
+            .map_or("", String::as_str),
+        restore_only: config.restore_only,
+        events: sdk_1_events_tx,
+    };
+    let sdk_2_connector = SdkConnector {
+        name: "sdk_2",
+        environment,
+        api_key: &config.breez_api_key,
+        working_dir: &sdk_2_working_dir,
+        partner_credentials,
+        mnemonic: config.sdk_2_mnemonic.as_deref(),
+        mnemonic_passphrase: config
+            .sdk_2_mnemonic_passphrase
+            .as_ref()
+            .or(config.mnemonic_passphrase.as_ref())
+            .map_or("", String::as_str),
+        restore_only: config.restore_only,
+        events: sdk_2_events_tx,
+    };
+    if let Some(Command::Sweep { node, to, yes }) = &cli.command {
+        let connector = match node {
+            Node::Sdk1 => &sdk_1_connector,
+            Node::Sdk2 => &sdk_2_connector,
+        };
+        let (sdk, _) = connector.connect().await?;
+        let res = sweep(&sdk, connector.name, to, *yes).await;
+
+        sdk.disconnect().await?;
+        if let Some(metrics_server) = metrics_server {
+            metrics_server.stop().await?;
+        }
+        return res;
+    }
+    if let Some(Command::CloseChannels { node, yes }) = &cli.command {
+        if !yes {
+            return Err(anyhow!("Closing the channels requires --yes"));
+        }
+        let connector = match node {
+            Node::Sdk1 => &sdk_1_connector,
+            Node::Sdk2 => &sdk_2_connector,
+        };
+        let (sdk, _) = connector.connect().await?;
+        let res = close_channels(&sdk, connector.name).await;
+
+        sdk.disconnect().await?;
+        if let Some(metrics_server) = metrics_server {
+            metrics_server.stop().await?;
+        }
+        return res;
+    }
+
+    let fiat_currency = config
+        .fiat_currency
+        .clone()
+        .unwrap_or(DEFAULT_FIAT_CURRENCY.into());
+    // Check the CSV columns before connecting, so a typo doesn't cost a node registration and a
+    // payment. The scenario names, and so the columns, don't depend on the fiat amount.
+    if let Some(columns) = &config.csv_columns {
+        check_csv_columns(columns, &config.scenarios(None), &fiat_currency)?;
+    }
+
+    let ((mut sdk_1, sdk_1_connect_time), (mut sdk_2, sdk_2_connect_time)) =
+        tokio::try_join!(sdk_1_connector.connect(), sdk_2_connector.connect())?;
+    info!("[sdk_1] Node info: {:?}", sdk_1.node_info()?);
     info!("[sdk_2] Node info: {:?}", sdk_2.node_info()?);
 
-    info!("Testing GL-2-WoS");
-    let gl2wos_res = pay_gl_2_ln_address(sdk_1.clone(), &config.ln_address_wos).await;
-    info!("Testing GL-2-GL");
-    let gl2gl_res = pay_gl_2_gl(sdk_1.clone(), sdk_2.clone()).await;
-    info!("Testing GL-2-Tor");
-    let gl2tor_res = pay_gl_2_ln_address(sdk_1.clone(), &config.ln_address_tor_node).await;
+    if let Some(Command::Balance) = cli.command {
+        print_balances(&sdk_1, "sdk_1")?;
+        print_balances(&sdk_2, "sdk_2")?;
+
+        sdk_1.disconnect().await?;
+        sdk_2.disconnect().await?;
+        if let Some(metrics_server) = metrics_server {
+            metrics_server.stop().await?;
+        }
+        return Ok(());
+    }
+
+    if cli.dry_run {
+        let all_resolved = check_ln_addresses(&config.ln_address_targets()).await;
+
+        sdk_1.disconnect().await?;
+        sdk_2.disconnect().await?;
+        if let Some(metrics_server) = metrics_server {
+            metrics_server.stop().await?;
+        }
+
+        if !all_resolved {
+            return Err(anyhow!("Dry run failed: not all LN Addresses resolved"));
+        }
+        info!("Dry run passed");
+        return Ok(());
+    }
+
+    let mut ctx = RunContext {
+        sdk_1: sdk_1.clone(),
+        sdk_2: sdk_2.clone(),
+        timeout_secs: config
+            .scenario_timeout_secs
+            .unwrap_or(DEFAULT_SCENARIO_TIMEOUT_SECS),
+        attempts: config.retry_attempts.unwrap_or(1),
+        iteration: 0,
+        deadline: None,
+        payment_permits: Semaphore::new(config.max_concurrent_payments().max(1)),
+        sdk_1_events: Mutex::new(sdk_1_events),
+        sdk_2_events: Mutex::new(sdk_2_events),
+    };
+    let fiat_amount_msat = match amount_fiat {
+        Some(amount_fiat) => {
+            let amount_msat = fiat_to_msat(&sdk_1, amount_fiat, &fiat_currency).await?;
+            info!("Paying {amount_fiat} {fiat_currency}, which is {amount_msat} msat");
+            Some(amount_msat)
+        }
+        None => None,
+    };
+    let scenarios = config.scenarios(fiat_amount_msat);
+    for name in config.enabled_scenarios.iter().flatten() {
+        if !scenarios.iter().any(|scenario| scenario.name() == name) {
+            error!("Enabled scenario {name} is not configured, ignoring it");
+        }
+    }
+    let mut sinks = config.sinks(&scenarios, &fiat_currency)?;
+    if let Some(loki) = loki {
+        sinks.push(Box::new(LokiSink::new(loki)));
+    }
+    let mut sink_writer = SinkWriter::spawn(sinks)?;
+    // Only after setting up the outputs, so a broken output config doesn't cost a payment
+    let cold_start_payment_ms = match &config.cold_start_scenario {
+        Some(name) => run_cold_start(&scenarios, name, &ctx).await?,
+        None => None,
+    };
+
+    let alert_threshold = config.alert_after_consecutive_failures.unwrap_or(1).max(1);
+    let mut alerts = Alerts::new(config.alert_channels());
+    // Warm-up iterations only make sense when running in a loop
+    let warmup_iterations = match config.loop_interval_secs {
+        Some(_) => config.warmup_iterations.unwrap_or(0),
+        None => 0,
+    };
+    // Load the baseline before running, so a broken file doesn't waste the whole run
+    let baseline = config
+        .baseline_path
+        .as_deref()
+        .map(Baseline::load)
+        .transpose()?;
+    let dashboard = cli.tui.then(|| Dashboard::start(shutdown.clone()));
+    // Only runs bounded by max_iterations can be resumed
+    let checkpoint_limit = config.loop_interval_secs.and(config.max_iterations);
+    let scenario_names: Vec<String> = scenarios.iter().map(|s| s.name().into()).collect();
+    let checkpoint = match checkpoint_limit {
+        Some(max_iterations) if !cli.fresh => Checkpoint::load(
+            CHECKPOINT_FILE_NAME,
+            &scenario_names,
+            max_iterations,
+            &latency_buckets_secs,
+        )?,
+        _ => None,
+    };
+    let mut completed = checkpoint.as_ref().map_or(0, |c| c.completed);
+    // A resumed run already did its warm-up, so continue numbering after the recorded iterations
+    let mut iteration = match checkpoint {
+        Some(_) => warmup_iterations + completed,
+        None => 0,
+    };
+    let mut stats = match checkpoint {
+        Some(checkpoint) => checkpoint.stats,
+        None => Stats::new(latency_buckets_secs),
+    };
+    // Logged on SIGUSR1 by a separate task, so that also works in the middle of an iteration
+    let (stats_snapshot, stats_snapshot_rx) = watch::channel(stats.clone());
+    tokio::spawn(log_stats_on_signal(stats_dump_signal, stats_snapshot_rx));
+    let run_metadata = RunMetadata::new();
+    let mut reconciliation = Reconciliation::default();
+    let mut last_iteration_res = None;
+    while !shutdown.is_cancelled() {
+        iteration += 1;
+        let iteration_ts = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        ctx.iteration = iteration;
+        // A node that can't be reconnected fails this iteration, the next one tries again
+        let mut connected = true;
+        let sdk_1_reconnected = match sdk_1_connector.ensure_connected(&mut sdk_1).await {
+            Ok(reconnected) => reconnected,
+            Err(e) => {
+                error!("{e}, retrying in the next iteration");
+                connected = false;
+                false
+            }
+        };
+        if sdk_1_reconnected {
+            ctx.sdk_1 = sdk_1.clone();
+        }
+        let sdk_2_reconnected = match sdk_2_connector.ensure_connected(&mut sdk_2).await {
+            Ok(reconnected) => reconnected,
+            Err(e) => {
+                error!("{e}, retrying in the next iteration");
+                connected = false;
+                false
+            }
+        };
+        if sdk_2_reconnected {
+            ctx.sdk_2 = sdk_2.clone();
+        }
+        ctx.drain_events().await;
+        let btc_rate = match fetch_btc_rate(&sdk_1, &fiat_currency).await {
+            Ok(btc_rate) => Some(btc_rate),
+            Err(e) => {
+                error!("Failed to fetch the BTC/{fiat_currency} rate: {e}");
+                None
+            }
+        };
+        let sender_balance_before_msat = log_balances(&sdk_1, "sdk_1");
+        let receiver_balance_before_msat = log_balances(&sdk_2, "sdk_2");
+
+        let sync_timeout = Duration::from_secs(
+            config
+                .sync_timeout_secs
+                .unwrap_or(DEFAULT_SYNC_TIMEOUT_SECS),
+        );
+        let nodes_ready = connected && wait_until_ready(&ctx, sync_timeout).await;
+        if connected && !nodes_ready {
+            error!("Nodes not synced within {sync_timeout:?}, skipping this iteration");
+        }
+
+        let sender_balance_low = match (config.min_sender_balance_msat, sender_balance_before_msat)
+        {
+            (Some(min_balance_msat), Some(balance_msat)) => balance_msat < min_balance_msat,
+            _ => false,
+        };
+        if sender_balance_low {
+            warn!(
+                "[sdk_1] Channels balance below {} msat, skipping the scenarios that spend from it",
+                config.min_sender_balance_msat.unwrap_or_default()
+            );
+            if connected && config.auto_topup_swapin == Some(true) {
+                top_up_swap_in(&sdk_1).await;
+            }
+        }
+        let preflight = Preflight {
+            connected,
+            nodes_ready,
+            sender_balance_low,
+        };
+
+        // Not counting the reconnects and syncing, so they don't eat into the scenarios' budget
+        ctx.deadline = iteration_deadline.map(|deadline| Instant::now() + deadline);
+        let observed_metrics = (iteration > warmup_iterations).then_some(metrics.as_ref());
+        let scenario_results =
+            run_scenarios(&scenarios, &ctx, &config, preflight, observed_metrics).await;
+
+        let iteration_res = Arc::new(IterationResult {
+            start_ts: iteration_ts,
+            iso_ts: iso_timestamp(iteration_ts),
+            metadata: run_metadata.clone(),
+            sdk1_connect_ms: sdk_1_connect_time.as_millis() as u64,
+            sdk2_connect_ms: sdk_2_connect_time.as_millis() as u64,
+            cold_start_payment_ms,
+            amount_fiat: config.amount_fiat,
+            fiat_amount_msat,
+            reconnected: sdk_1_reconnected || sdk_2_reconnected,
+            btc_rate,
+            sender_balance_before_msat,
+            sender_balance_after_msat: log_balances(&sdk_1, "sdk_1"),
+            receiver_balance_before_msat,
+            receiver_balance_after_msat: log_balances(&sdk_2, "sdk_2"),
+            scenarios: scenario_results,
+        });
+
+        if iteration <= warmup_iterations {
+            debug!("Warm-up iteration {iteration}/{warmup_iterations}: {iteration_res:?}");
+        } else {
+            stats.record(&iteration_res);
+            stats_snapshot.send_replace(stats.clone());
+            reconciliation.record(&iteration_res);
+            for res in &iteration_res.scenarios {
+                let failure_streak = stats
+                    .scenario(&res.name)
+                    .map(|scenario_stats| scenario_stats.consecutive_failures)
+                    .unwrap_or_default();
+                if failure_streak < alert_threshold {
+                    continue;
+                }
+                if failure_streak == alert_threshold {
+                    error!("{} failed {failure_streak} times in a row", res.name);
+                }
+                alerts.notify(res, iteration_ts, failure_streak).await;
+            }
+
+            if let Err(e) = sink_writer.record(iteration_res.clone()).await {
+                error!("Failed to queue the iteration result for the sinks: {e}");
+            }
+            stats.log_success_rates();
+            completed += 1;
+            if let Some(max_iterations) = checkpoint_limit {
+                if let Err(e) = Checkpoint::save(
+                    CHECKPOINT_FILE_NAME,
+                    &scenario_names,
+                    max_iterations,
+                    completed,
+                    &stats,
+                ) {
+                    error!("Failed to save the checkpoint: {e}");
+                }
+            }
+            metrics.observe_stats(&stats);
+            if let Some(dashboard) = &dashboard {
+                dashboard.update(&iteration_res);
+            }
+        }
+        last_iteration_res = Some(iteration_res);
+
+        let Some(interval_secs) = config.loop_interval_secs else {
+            break;
+        };
+        if let Some(max_iterations) = config.max_iterations {
+            if completed >= max_iterations {
+                info!("Reached the limit of {max_iterations} iterations");
+                Checkpoint::remove(CHECKPOINT_FILE_NAME)?;
+                break;
+            }
+        }
+        let interval_secs = jittered(interval_secs, config.loop_jitter_secs.unwrap_or(0));
+        info!("Next iteration in {interval_secs} seconds");
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(interval_secs)) => {}
+            _ = shutdown.cancelled() => {}
+        }
+    }
+
+    // Restore the terminal before printing the summary
+    drop(dashboard);
+    stats.log_summary();
+    if let (false, Some(last_iteration_res)) = (cli.quiet, &last_iteration_res) {
+        report::print_summary_table(last_iteration_res, &stats);
+    }
+    sink_writer.finish().await?;
+
+    if let Err(e) = reconciliation.check(&sdk_1).await {
+        error!("Failed to reconcile the payments with the node's history: {e}");
+    }
 
     sdk_1.disconnect().await?;
     sdk_2.disconnect().await?;
 
-    let file = OpenOptions::new()
-        .append(true)
-        .create(true)
-        .open(config.iterations_csv_full_path)?;
-    let mut wtr = csv::Writer::from_writer(file);
-    wtr.write_record(&[
-        start_ts.to_string(),
-        gl2wos_res.0.map(|d| d.to_string()).unwrap_or_default(),
-        gl2wos_res.1,
-        gl2gl_res.0.map(|d| d.to_string()).unwrap_or_default(),
-        gl2gl_res.1,
-        gl2tor_res.0.map(|d| d.to_string()).unwrap_or_default(),
-        gl2tor_res.1,
-    ])?;
-    wtr.flush()?;
+    if let Some(metrics_server) = metrics_server {
+        metrics_server.stop().await?;
+    }
+
+    if let Some(baseline) = baseline {
+        baseline.check(
+            &stats,
+            config
+                .regression_factor
+                .unwrap_or(DEFAULT_REGRESSION_FACTOR),
+        )?;
+    }
 
     Ok(())
 }
 
-/// Build result tuple for a successful test
-fn test_ok(ts_start: Instant) -> (Option<u64>, String) {
-    (
-        Some(Instant::now().duration_since(ts_start).as_secs()),
-        "Ok".into(),
-    )
+/// Check that every LN Address resolves to an LNURL-pay endpoint, logging the outcome per target.
+///
+/// Returns whether all of them resolved.
+async fn check_ln_addresses(targets: &[LnAddressTarget]) -> bool {
+    let mut all_resolved = true;
+    for target in targets {
+        match parse(&target.address).await {
+            Ok(InputType::LnUrlPay { .. }) => info!("[{}] PASS: {}", target.name, target.address),
+            Ok(input_type) => {
+                error!(
+                    "[{}] FAIL: {} is not an LNURL-pay endpoint, got {}",
+                    target.name,
+                    target.address,
+                    input_type_name(&input_type)
+                );
+                all_resolved = false;
+            }
+            Err(e) => {
+                error!(
+                    "[{}] FAIL: {} could not be parsed: {e}",
+                    target.name, target.address
+                );
+                all_resolved = false;
+            }
+        }
+    }
+    all_resolved
 }
 
-/// Build result tuple for a failed test
-fn test_err(err: &str) -> (Option<u64>, String) {
-    error!("{err}");
-    (None, err.to_string())
-}
-
-async fn pay_gl_2_ln_address(
-    sdk_sender: Arc<BreezServices>,
-    ln_address: &str,
-) -> (Option<u64>, String) {
-    match parse(ln_address).await {
-        Ok(InputType::LnUrlPay { data }) => {
-            let ts_start = Instant::now();
-            match sdk_sender
-                .lnurl_pay(LnUrlPayRequest {
-                    data,
-                    amount_msat: 1_000,
-                    comment: Some("test-gl2lnurl".into()),
-                    payment_label: None,
-                })
-                .await
-            {
-                // LNURL-pay success case
-                Ok(LnUrlPayResult::EndpointSuccess { .. }) => test_ok(ts_start),
+/// Price of 1 BTC in the fiat currency, with the current exchange rate of the SDK
+async fn fetch_btc_rate(sdk: &BreezServices, currency: &str) -> Result<f64> {
+    let rates = sdk.fetch_fiat_rates().await?;
+    let btc_rate = rates
+        .iter()
+        .find(|rate| rate.coin.eq_ignore_ascii_case(currency))
+        .map(|rate| rate.value)
+        .ok_or_else(|| anyhow!("No exchange rate found for {currency}"))?;
+    if !btc_rate.is_finite() || btc_rate <= 0.0 {
+        return Err(anyhow!("Invalid exchange rate for {currency}: {btc_rate}"));
+    }
+    Ok(btc_rate)
+}
 
-                // LNURL-pay failure cases
-                Ok(LnUrlPayResult::EndpointError { data }) => test_err(&data.reason),
-                Ok(LnUrlPayResult::PayError { data }) => test_err(&data.reason),
-                Err(e) => test_err(&e.to_string()),
+/// Convert a fiat amount to msat, with the current exchange rate of the SDK
+async fn fiat_to_msat(sdk: &BreezServices, amount_fiat: f64, currency: &str) -> Result<u64> {
+    let btc_rate = fetch_btc_rate(sdk, currency).await?;
+
+    // 1 BTC is 100_000_000_000 msat
+    let amount_msat = (amount_fiat / btc_rate * 100_000_000_000.0).round() as u64;
+    if amount_msat == 0 {
+        return Err(anyhow!(
+            "{amount_fiat} {currency} is less than 1 msat at {btc_rate} {currency}/BTC"
+        ));
+    }
+    Ok(amount_msat)
+}
+
+/// Randomly shift `secs` by up to `jitter_secs` in either direction, without going below 0
+fn jittered(secs: u64, jitter_secs: u64) -> u64 {
+    let mut rng = rand::thread_rng();
+    let jitter = rng.gen_range(0..=jitter_secs);
+    if rng.gen() {
+        secs.saturating_add(jitter)
+    } else {
+        secs.saturating_sub(jitter)
+    }
+}
+
+/// Checks done before running the scenarios of an iteration, which can cause some to be skipped
+#[derive(Clone, Copy)]
+struct Preflight {
+    /// Whether both SDKs are connected, false if reconnecting one of them failed
+    connected: bool,
+    /// Whether both nodes are synced
+    nodes_ready: bool,
+    /// Whether the sender balance is below `min_sender_balance_msat`
+    sender_balance_low: bool,
+}
+
+/// Run the scenarios of one iteration, returning their results in the order of `scenarios`.
+///
+/// With `parallel_scenarios`, all scenarios except the [Scenario::exclusive] ones run concurrently,
+/// followed by the exclusive ones one at a time.
+///
+/// Scenarios run one after the other are separated by `inter_scenario_delay_ms`.
+///
+/// Each result is recorded in `metrics` as soon as its scenario finished, if given.
+async fn run_scenarios(
+    scenarios: &[Box<dyn Scenario>],
+    ctx: &RunContext,
+    config: &PulseConfig,
+    preflight: Preflight,
+    metrics: Option<&Metrics>,
+) -> Vec<ScenarioResult> {
+    let delay = Duration::from_millis(config.inter_scenario_delay_ms.unwrap_or(0));
+    if config.parallel_scenarios != Some(true) {
+        let mut results = vec![];
+        for (idx, scenario) in scenarios.iter().enumerate() {
+            if idx > 0 {
+                tokio::time::sleep(delay).await;
             }
+            results.push(
+                run_observed_scenario(scenario.as_ref(), ctx, config, preflight, metrics).await,
+            );
         }
-        Ok(InputType::LnUrlError { data }) => test_err(&format!("LNURL error: {}", data.reason)),
-        _ => test_err("Failed to parse LN Address"),
+        return results;
     }
+
+    let (exclusive, concurrent): (Vec<_>, Vec<_>) = scenarios
+        .iter()
+        .enumerate()
+        .partition(|(_, scenario)| scenario.exclusive());
+    let mut results = join_all(concurrent.into_iter().map(|(idx, scenario)| async move {
+        let res = run_observed_scenario(scenario.as_ref(), ctx, config, preflight, metrics).await;
+        (idx, res)
+    }))
+    .await;
+    for (idx, scenario) in exclusive {
+        if !results.is_empty() {
+            tokio::time::sleep(delay).await;
+        }
+        let res = run_observed_scenario(scenario.as_ref(), ctx, config, preflight, metrics).await;
+        results.push((idx, res));
+    }
+    results.sort_by_key(|(idx, _)| *idx);
+    results.into_iter().map(|(_, res)| res).collect()
+}
+
+/// Run the scenario and record its result in `metrics`, if given
+async fn run_observed_scenario(
+    scenario: &dyn Scenario,
+    ctx: &RunContext,
+    config: &PulseConfig,
+    preflight: Preflight,
+    metrics: Option<&Metrics>,
+) -> ScenarioResult {
+    let res = run_scenario(scenario, ctx, config, preflight).await;
+    if let Some(metrics) = metrics {
+        for measurement in res.samples() {
+            metrics.observe(&res.name, measurement);
+        }
+    }
+    res
+}
+
+/// Run the scenario named `name` once, returning its duration if it succeeded
+async fn run_cold_start(
+    scenarios: &[Box<dyn Scenario>],
+    name: &str,
+    ctx: &RunContext,
+) -> Result<Option<u64>> {
+    let Some(scenario) = scenarios.iter().find(|scenario| scenario.name() == name) else {
+        return Err(anyhow!("cold_start_scenario {name} is not configured"));
+    };
+
+    info!("Testing {name} right after connecting");
+    let res = scenario.run(ctx).await;
+    let duration_ms = res
+        .measurement
+        .duration_ms()
+        .filter(|_| res.measurement.is_ok());
+    match duration_ms {
+        Some(duration_ms) => info!("Cold start {name} took {duration_ms}ms"),
+        None => error!("Cold start {name} failed: {}", res.measurement.status),
+    }
+    Ok(duration_ms)
+}
+
+/// Run the scenario, unless the nodes aren't ready, it's disabled in the config, it would spend
+/// from a sender with a too low balance or the iteration deadline is near.
+///
+/// If an SDK couldn't be reconnected, enabled scenarios fail without running.
+async fn run_scenario(
+    scenario: &dyn Scenario,
+    ctx: &RunContext,
+    config: &PulseConfig,
+    preflight: Preflight,
+) -> ScenarioResult {
+    if !preflight.connected && config.is_enabled(scenario.name()) {
+        let msg = format!(
+            "Not running {}, an SDK couldn't be reconnected",
+            scenario.name()
+        );
+        return ScenarioResult::new(
+            scenario.name(),
+            Measurement::err(FailureKind::Disconnected, &msg),
+        );
+    }
+    let skipped_reason = if !preflight.nodes_ready {
+        Some("nodes not synced")
+    } else if !config.is_enabled(scenario.name()) {
+        Some("disabled in config")
+    } else if preflight.sender_balance_low && scenario.spends_sender_balance() {
+        Some("sender balance below min_sender_balance_msat")
+    } else if ctx.deadline_near() {
+        warn!("Iteration deadline near, not starting {}", scenario.name());
+        Some("iteration deadline reached")
+    } else {
+        None
+    };
+    if let Some(reason) = skipped_reason {
+        return ScenarioResult::new(scenario.name(), Measurement::skipped(reason));
+    }
+
+    // Only fails if the semaphore was closed, which it never is
+    let _permit = if scenario.issues_payment() {
+        ctx.payment_permits.acquire().await.ok()
+    } else {
+        None
+    };
+    let repetitions = config.repetitions_per_scenario.unwrap_or(1).max(1);
+    let mut measurements = vec![];
+    for repetition in 1..=repetitions {
+        if repetition > 1 && ctx.deadline_near() {
+            warn!(
+                "Iteration deadline near, stopping {} after {} repetitions",
+                scenario.name(),
+                repetition - 1
+            );
+            break;
+        }
+        if repetitions > 1 {
+            info!("Testing {} ({repetition}/{repetitions})", scenario.name());
+        } else {
+            info!("Testing {}", scenario.name());
+        }
+        measurements.push(scenario.run(ctx).await.measurement);
+    }
+    ScenarioResult::aggregate(
+        scenario.name(),
+        measurements,
+        config.aggregation.unwrap_or_default(),
+    )
 }
 
-async fn pay_gl_2_gl(
-    sdk_sender: Arc<BreezServices>,
-    sdk_receiver: Arc<BreezServices>,
-) -> (Option<u64>, String) {
-    info!("[sdk-rx] Creating invoice");
-    match sdk_receiver
-        .receive_payment(ReceivePaymentRequest {
-            amount_msat: 1_000,
-            description: "test-gl2gl".to_string(),
-            preimage: None,
+/// Wait until both nodes are ready to run the scenarios, re-checking whenever one of them synced.
+///
+/// Returns false if they weren't ready within `timeout`.
+async fn wait_until_ready(ctx: &RunContext, timeout: Duration) -> bool {
+    let check = async {
+        let mut sdk_1_events = ctx.sdk_1_events.lock().await;
+        let mut sdk_2_events = ctx.sdk_2_events.lock().await;
+        loop {
+            match nodes_ready(ctx.sdk_1.as_ref(), ctx.sdk_2.as_ref()) {
+                Ok(true) => return,
+                Ok(false) => info!("Nodes not ready yet, waiting for them to sync"),
+                Err(e) => error!("Failed to get node info: {e}"),
+            }
+            tokio::select! {
+                _ = wait_for_synced(&mut sdk_1_events) => {}
+                _ = wait_for_synced(&mut sdk_2_events) => {}
+            }
+        }
+    };
+    tokio::time::timeout(timeout, check).await.is_ok()
+}
+
+/// Whether both nodes are connected to a peer (their LSP) and neither is more than
+/// [MAX_BLOCKS_BEHIND] blocks behind the other one
+fn nodes_ready(sdk_1: &dyn SdkClient, sdk_2: &dyn SdkClient) -> Result<bool> {
+    let node_states = [sdk_1.node_info()?, sdk_2.node_info()?];
+    let best_block_height = node_states
+        .iter()
+        .map(|node_state| node_state.block_height)
+        .max()
+        .unwrap_or_default();
+    Ok(node_states.iter().all(|node_state| {
+        !node_state.connected_peers.is_empty()
+            && node_state.block_height + MAX_BLOCKS_BEHIND >= best_block_height
+    }))
+}
+
+/// Resolves on the next [BreezEvent::Synced] event, or never if the SDK is gone
+async fn wait_for_synced(events: &mut mpsc::UnboundedReceiver<BreezEvent>) {
+    while let Some(event) = events.recv().await {
+        if let BreezEvent::Synced = event {
+            return;
+        }
+    }
+    std::future::pending::<()>().await;
+}
+
+/// Create a swap-in address on the sender, or reuse the one of the swap in progress, and log it so
+/// it can be funded
+async fn top_up_swap_in(sdk: &BreezServices) {
+    match sdk.in_progress_swap().await {
+        Ok(Some(swap_info)) => {
+            warn!(
+                "[sdk_1] Swap-in already in progress, send funds to {} to top up",
+                swap_info.bitcoin_address
+            );
+            return;
+        }
+        Ok(None) => {}
+        Err(e) => {
+            error!("[sdk_1] Failed to get the swap in progress: {e}");
+            return;
+        }
+    }
+
+    match sdk
+        .receive_onchain(ReceiveOnchainRequest {
             opening_fee_params: None,
-            use_description_hash: None,
-            expiry: Some(60), // Small expiration time, so VLS can prune older invoices
-            cltv: None,
         })
         .await
     {
-        Ok(recv_payment) => {
-            let ts_start = Instant::now();
+        Ok(swap_info) => warn!(
+            "[sdk_1] Created swap-in address, send funds to {} to top up",
+            swap_info.bitcoin_address
+        ),
+        Err(e) => error!("[sdk_1] Failed to create a swap-in address for the top-up: {e}"),
+    }
+}
+
+/// Register a new node in `working_dir` and print its node ID. The generated mnemonic is printed
+/// and saved in the working dir once the node is registered, so a failed registration can simply
+/// be retried.
+async fn new_node(
+    config: &PulseConfig,
+    invite_code: Option<&str>,
+    working_dir: &str,
+) -> Result<()> {
+    let mnemonic_path = format!("{working_dir}/{MNEMONIC_FILE_NAME}");
+    if std::path::Path::new(&mnemonic_path).exists() {
+        return Err(anyhow!(
+            "{mnemonic_path} already exists, refusing to create another node in {working_dir}"
+        ));
+    }
+
+    let partner_credentials = config.partner_credentials()?;
+    if invite_code.is_none() && partner_credentials.is_none() {
+        return Err(anyhow!(
+            "Either --invite-code or partner credentials are needed to register a node"
+        ));
+    }
+
+    std::fs::create_dir_all(working_dir)?;
+    let mnemonic = Mnemonic::generate_in(Language::English, 12)?;
+    let seed = mnemonic.to_seed(config.mnemonic_passphrase.as_deref().unwrap_or(""));
+    // Nobody waits for the events of this SDK
+    let (events, _) = mpsc::unbounded_channel();
+    let (sdk, _) = get_sdk(
+        config.environment()?,
+        &config.breez_api_key,
+        working_dir,
+        GreenlightNodeConfig {
+            partner_credentials,
+            invite_code: invite_code.map(Into::into),
+        },
+        seed.to_vec(),
+        Some(false),
+        events,
+    )
+    .await?;
+    // Printed before saving it, so it isn't lost if that fails
+    println!("Generated mnemonic: {mnemonic}");
+    let saved = save_mnemonic(&mnemonic_path, &mnemonic);
+    let node_info = sdk.node_info();
+    sdk.disconnect().await?;
+    saved?;
+    info!("Saved generated mnemonic to {mnemonic_path}");
+
+    println!("Node ID: {}", node_info?.id);
+    Ok(())
+}
+
+/// Share of the spendable balance, in percent, left for the routing fees when sweeping to an invoice
+const SWEEP_FEE_RESERVE_PERCENT: u64 = 1;
+
+/// Send the spendable balance of the node to a BOLT11 invoice or, through a reverse swap, to an
+/// on-chain address. Unless `confirmed`, only log what would be sent.
+async fn sweep(sdk: &BreezServices, sdk_name: &str, to: &str, confirmed: bool) -> Result<()> {
+    match parse(to).await? {
+        InputType::Bolt11 { invoice } => {
+            if invoice.amount_msat.is_some() {
+                return Err(anyhow!(
+                    "The invoice must not have an amount, so the whole balance can be sent"
+                ));
+            }
+            let max_payable_msat = sdk.node_info()?.max_payable_msat;
+            let amount_msat = max_payable_msat - max_payable_msat * SWEEP_FEE_RESERVE_PERCENT / 100;
+            if amount_msat == 0 {
+                return Err(anyhow!("[{sdk_name}] Nothing to sweep"));
+            }
+            if !confirmed {
+                info!(
+                    "[{sdk_name}] Would pay {amount_msat} msat to the invoice, pass --yes to send"
+                );
+                return Ok(());
+            }
 
-            info!("[sdk-tx] Paying invoice");
-            match sdk_sender
+            info!("[{sdk_name}] Paying {amount_msat} msat to the invoice");
+            let res = sdk
                 .send_payment(SendPaymentRequest {
-                    bolt11: recv_payment.ln_invoice.bolt11,
-                    amount_msat: None,
+                    bolt11: invoice.bolt11,
+                    amount_msat: Some(amount_msat),
                     label: None,
                 })
-                .await
-            {
-                Ok(_) => test_ok(ts_start),
-                Err(e) => test_err(&format!("[sdk-tx] Failed to send payment: {e}")),
+                .await?;
+            info!(
+                "[{sdk_name}] Swept {} msat, paying {} msat in fees",
+                res.payment.amount_msat, res.payment.fee_msat
+            );
+        }
+        InputType::BitcoinAddress { address } => {
+            let limits = sdk.onchain_payment_limits().await?;
+            if limits.max_payable_sat < limits.min_sat {
+                return Err(anyhow!(
+                    "[{sdk_name}] Spendable balance of {} sat is below the reverse swap minimum of {} sat",
+                    limits.max_payable_sat,
+                    limits.min_sat
+                ));
+            }
+            let claim_tx_feerate = sdk.recommended_fees().await?.hour_fee as u32;
+            let prepare_res = sdk
+                .prepare_onchain_payment(PrepareOnchainPaymentRequest {
+                    amount_sat: limits.max_payable_sat,
+                    amount_type: SwapAmountType::Send,
+                    claim_tx_feerate,
+                })
+                .await?;
+            if !confirmed {
+                info!(
+                    "[{sdk_name}] Would send {} sat to {}, of which {} sat arrive after fees, pass --yes to send",
+                    prepare_res.sender_amount_sat,
+                    address.address,
+                    prepare_res.recipient_amount_sat
+                );
+                return Ok(());
+            }
+
+            info!(
+                "[{sdk_name}] Sending {} sat to {} through a reverse swap",
+                prepare_res.sender_amount_sat, address.address
+            );
+            let res = sdk
+                .pay_onchain(PayOnchainRequest {
+                    recipient_address: address.address,
+                    prepare_res,
+                })
+                .await?;
+            info!(
+                "[{sdk_name}] Reverse swap {} initiated, the funds arrive once it's claimed",
+                res.reverse_swap_info.id
+            );
+        }
+        input_type => {
+            return Err(anyhow!(
+                "Can only sweep to a BOLT11 invoice or an on-chain address, got {}",
+                input_type_name(&input_type)
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// Cooperatively close all channels of the node with the LSP, logging the closing transaction of
+/// each. The funds then arrive in the node's on-chain balance.
+async fn close_channels(sdk: &BreezServices, sdk_name: &str) -> Result<()> {
+    info!("[{sdk_name}] Closing all channels with the LSP");
+    let tx_ids = sdk.close_lsp_channels().await?;
+    if tx_ids.is_empty() {
+        info!("[{sdk_name}] No open channels to close");
+    }
+    for tx_id in &tx_ids {
+        info!("[{sdk_name}] Closing channel in transaction {tx_id}");
+    }
+    Ok(())
+}
+
+/// Format the Unix timestamp as RFC 3339 in UTC, like `2024-05-01T12:00:00Z`
+fn iso_timestamp(ts: u64) -> String {
+    DateTime::from_timestamp(ts as i64, 0)
+        .map(|date| date.to_rfc3339_opts(SecondsFormat::Secs, true))
+        .unwrap_or_default()
+}
+
+/// Print the node ID, balances and peer count of the node to stdout
+fn print_balances(sdk: &BreezServices, sdk_name: &str) -> Result<()> {
+    let node_info = sdk.node_info()?;
+    println!("{sdk_name}");
+    println!("  Node ID:             {}", node_info.id);
+    println!(
+        "  Channels balance:    {} msat",
+        node_info.channels_balance_msat
+    );
+    println!(
+        "  Onchain balance:     {} msat",
+        node_info.onchain_balance_msat
+    );
+    println!(
+        "  Inbound liquidity:   {} msat",
+        node_info.inbound_liquidity_msats
+    );
+    println!("  Connected peers:     {}", node_info.connected_peers.len());
+    Ok(())
+}
+
+/// Log the channels and onchain balances of the node, returning the channels balance
+fn log_balances(sdk: &BreezServices, sdk_name: &str) -> Option<u64> {
+    match sdk.node_info() {
+        Ok(node_info) => {
+            info!(
+                "[{sdk_name}] Channels balance: {} msat, onchain balance: {} msat",
+                node_info.channels_balance_msat, node_info.onchain_balance_msat
+            );
+            Some(node_info.channels_balance_msat)
+        }
+        Err(e) => {
+            error!("[{sdk_name}] Failed to get node info: {e}");
+            None
+        }
+    }
+}
+
+/// Resolves when the process receives SIGINT (Ctrl-C) or, on Unix, SIGTERM
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = sigterm.recv() => {}
+                }
+                return;
+            }
+            Err(e) => error!("Failed to listen for SIGTERM: {e}"),
+        }
+    }
+
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        error!("Failed to listen for Ctrl-C: {e}");
+        // Never resolve, so a broken signal handler doesn't stop the run
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Log the latest stats every time they're requested with SIGUSR1
+async fn log_stats_on_signal(mut signal: StatsDumpSignal, stats: watch::Receiver<Stats>) {
+    loop {
+        signal.recv().await;
+        stats.borrow().log_summary();
+    }
+}
+
+/// Requests to log the current stats, sent with SIGUSR1. Never received on platforms without it.
+struct StatsDumpSignal {
+    #[cfg(unix)]
+    signal: Option<tokio::signal::unix::Signal>,
+}
+
+impl StatsDumpSignal {
+    #[cfg(unix)]
+    fn new() -> Self {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let signal = signal(SignalKind::user_defined1())
+            .map_err(|e| error!("Failed to listen for SIGUSR1: {e}"))
+            .ok();
+        Self { signal }
+    }
+
+    #[cfg(not(unix))]
+    fn new() -> Self {
+        Self {}
+    }
+
+    async fn recv(&mut self) {
+        #[cfg(unix)]
+        if let Some(signal) = &mut self.signal {
+            if signal.recv().await.is_some() {
+                return;
             }
+            self.signal = None;
         }
-        Err(e) => test_err(&format!("[sdk-rx] Failed to create invoice: {e}")),
+        std::future::pending::<()>().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdk_client::mock::MockSdkClient;
+
+    /// Fails after connecting, like `main()` returning early with an error
+    async fn fail_after_connecting(sdk: Arc<MockSdkClient>) -> Result<()> {
+        let _sdk = SdkGuard::new(sdk, "sdk_1");
+        Err(anyhow!("Forced failure"))
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn guard_disconnects_on_error_path() {
+        let sdk = Arc::new(MockSdkClient::default());
+        assert!(fail_after_connecting(sdk.clone()).await.is_err());
+        assert_eq!(sdk.disconnects(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn guard_disconnects_only_once() {
+        let sdk = Arc::new(MockSdkClient::default());
+        SdkGuard::new(sdk.clone(), "sdk_1")
+            .disconnect()
+            .await
+            .unwrap();
+        assert_eq!(sdk.disconnects(), 1);
     }
 }
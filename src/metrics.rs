@@ -0,0 +1,162 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use axum::routing::get;
+use axum::Router;
+use log::info;
+use prometheus::{
+    Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use crate::scenarios::Measurement;
+use crate::stats::Stats;
+
+/// Prometheus metrics updated after each scenario
+pub(crate) struct Metrics {
+    registry: Registry,
+    payment_duration_seconds: GaugeVec,
+    payment_latency_seconds: HistogramVec,
+    payment_total: IntCounterVec,
+    success_rate_percent: GaugeVec,
+    availability_ratio: GaugeVec,
+}
+
+impl Metrics {
+    /// The latency histogram uses the `bucket_bounds_secs` as upper bounds, plus a `+Inf` bucket
+    pub(crate) fn new(bucket_bounds_secs: &[f64]) -> Result<Self> {
+        let registry = Registry::new();
+
+        let payment_duration_seconds = GaugeVec::new(
+            Opts::new(
+                "sdk_pulse_payment_duration_seconds",
+                "Duration of the last successful payment",
+            ),
+            &["scenario"],
+        )?;
+        registry.register(Box::new(payment_duration_seconds.clone()))?;
+
+        let payment_latency_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "sdk_pulse_payment_latency_seconds",
+                "Duration of the successful payments",
+            )
+            .buckets(bucket_bounds_secs.to_vec()),
+            &["scenario"],
+        )?;
+        registry.register(Box::new(payment_latency_seconds.clone()))?;
+
+        let payment_total = IntCounterVec::new(
+            Opts::new("sdk_pulse_payment_total", "Number of attempted payments"),
+            &["scenario", "status"],
+        )?;
+        registry.register(Box::new(payment_total.clone()))?;
+
+        let success_rate_percent = GaugeVec::new(
+            Opts::new(
+                "sdk_pulse_success_rate_percent",
+                "Percentage of successful runs since start",
+            ),
+            &["scenario"],
+        )?;
+        registry.register(Box::new(success_rate_percent.clone()))?;
+
+        let availability_ratio = GaugeVec::new(
+            Opts::new(
+                "sdk_pulse_availability_ratio",
+                "Ratio of the runs since start that succeeded within the timeout, excluding skipped runs",
+            ),
+            &["scenario"],
+        )?;
+        registry.register(Box::new(availability_ratio.clone()))?;
+
+        Ok(Self {
+            registry,
+            payment_duration_seconds,
+            payment_latency_seconds,
+            payment_total,
+            success_rate_percent,
+            availability_ratio,
+        })
+    }
+
+    /// Record the result of a finished scenario
+    pub(crate) fn observe(&self, scenario: &str, res: &Measurement) {
+        if let Some(duration) = res.duration {
+            self.payment_duration_seconds
+                .with_label_values(&[scenario])
+                .set(duration.as_secs_f64());
+            self.payment_latency_seconds
+                .with_label_values(&[scenario])
+                .observe(duration.as_secs_f64());
+        }
+        self.payment_total
+            .with_label_values(&[scenario, res.outcome.as_str()])
+            .inc();
+    }
+
+    /// Update the success rates and availabilities from the stats collected so far
+    pub(crate) fn observe_stats(&self, stats: &Stats) {
+        for (scenario, scenario_stats) in stats.scenarios() {
+            if let Some(success_rate) = scenario_stats.success_rate() {
+                self.success_rate_percent
+                    .with_label_values(&[scenario])
+                    .set(success_rate);
+            }
+            if let Some(availability) = scenario_stats.availability() {
+                self.availability_ratio
+                    .with_label_values(&[scenario])
+                    .set(availability);
+            }
+        }
+    }
+
+    /// Render all metrics in the Prometheus text format
+    fn render(&self) -> String {
+        let mut buf = vec![];
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buf) {
+            return format!("# Failed to encode metrics: {e}");
+        }
+        String::from_utf8(buf).unwrap_or_default()
+    }
+}
+
+/// HTTP server exposing [Metrics] on `/metrics`
+pub(crate) struct MetricsServer {
+    shutdown_tx: oneshot::Sender<()>,
+    handle: JoinHandle<std::io::Result<()>>,
+}
+
+impl MetricsServer {
+    pub(crate) async fn start(bind_addr: &str, metrics: Arc<Metrics>) -> Result<Self> {
+        let listener = TcpListener::bind(bind_addr).await?;
+        info!(
+            "Serving metrics on http://{}/metrics",
+            listener.local_addr()?
+        );
+
+        let app = Router::new().route("/metrics", get(move || async move { metrics.render() }));
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let handle = tokio::spawn(async move {
+            axum::serve(listener, app)
+                .with_graceful_shutdown(async {
+                    shutdown_rx.await.ok();
+                })
+                .await
+        });
+
+        Ok(Self {
+            shutdown_tx,
+            handle,
+        })
+    }
+
+    /// Stop accepting connections and wait for the server task to finish
+    pub(crate) async fn stop(self) -> Result<()> {
+        self.shutdown_tx.send(()).ok();
+        self.handle.await??;
+        Ok(())
+    }
+}
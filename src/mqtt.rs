@@ -0,0 +1,180 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use log::{debug, error};
+use rumqttc::{AsyncClient, Event, MqttOptions, Outgoing, QoS};
+use tokio::task::JoinHandle;
+
+use crate::sinks::Sink;
+use crate::IterationResult;
+
+/// Port used if the broker address doesn't include one
+const DEFAULT_MQTT_PORT: u16 = 1883;
+/// How many messages are buffered while the broker is unreachable, newer ones are dropped
+const MQTT_QUEUE_LEN: usize = 100;
+/// How long to wait before polling the connection again after an error, which reconnects
+const MQTT_RECONNECT_DELAY: Duration = Duration::from_secs(5);
+/// How long to wait for the queued messages to be sent when stopping
+const MQTT_DISCONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Publishes the duration and status of every scenario to an MQTT broker, as retained messages on
+/// `{topic}/{scenario}/duration_ms` and `{topic}/{scenario}/status`
+pub(crate) struct MqttSink {
+    client: AsyncClient,
+    topic: String,
+    /// Drives the connection to the broker, reconnecting when it's lost
+    event_loop: Option<JoinHandle<()>>,
+}
+
+impl MqttSink {
+    /// `broker` is the address of the broker, see [parse_broker], `credentials` an optional
+    /// username and password
+    pub(crate) fn new(
+        broker: &str,
+        topic: &str,
+        credentials: Option<(&str, &str)>,
+    ) -> Result<Self> {
+        let (host, port) = parse_broker(broker)?;
+        let mut options = MqttOptions::new(format!("sdk_pulse-{}", std::process::id()), host, port);
+        if let Some((username, password)) = credentials {
+            options.set_credentials(username, password);
+        }
+
+        let (client, mut event_loop) = AsyncClient::new(options, MQTT_QUEUE_LEN);
+        let event_loop = tokio::spawn(async move {
+            loop {
+                match event_loop.poll().await {
+                    Ok(Event::Outgoing(Outgoing::Disconnect)) => break,
+                    Ok(event) => debug!("MQTT event: {event:?}"),
+                    Err(e) => {
+                        error!(
+                            "MQTT connection error, reconnecting in {MQTT_RECONNECT_DELAY:?}: {e}"
+                        );
+                        tokio::time::sleep(MQTT_RECONNECT_DELAY).await;
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            client,
+            topic: topic.trim_end_matches('/').into(),
+            event_loop: Some(event_loop),
+        })
+    }
+
+    /// Queue a retained message, dropping it if the queue is full because the broker is unreachable
+    fn publish(&self, topic: String, payload: String) {
+        if let Err(e) = self
+            .client
+            .try_publish(&topic, QoS::AtLeastOnce, true, payload)
+        {
+            error!("Dropping MQTT message for {topic}: {e}");
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for MqttSink {
+    fn name(&self) -> &'static str {
+        "MQTT"
+    }
+
+    async fn record(&mut self, iteration_res: &IterationResult) -> Result<()> {
+        for scenario in &iteration_res.scenarios {
+            let res = &scenario.measurement;
+            let prefix = format!("{}/{}", self.topic, scenario.name);
+            if let Some(duration_ms) = res.duration_ms() {
+                self.publish(format!("{prefix}/duration_ms"), duration_ms.to_string());
+            }
+            self.publish(format!("{prefix}/status"), res.status.clone());
+        }
+        Ok(())
+    }
+
+    /// Send the queued messages and disconnect from the broker
+    async fn flush(&mut self) -> Result<()> {
+        if let Err(e) = self.client.disconnect().await {
+            error!("Failed to disconnect from the MQTT broker: {e}");
+        }
+        if let Some(event_loop) = self.event_loop.take() {
+            if tokio::time::timeout(MQTT_DISCONNECT_TIMEOUT, event_loop)
+                .await
+                .is_err()
+            {
+                error!("MQTT messages not sent within {MQTT_DISCONNECT_TIMEOUT:?}, dropping them");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Split the broker address into host and port. It's the `host` or `host:port` of the broker, with
+/// an IPv6 address in brackets if it has a port, like `[::1]:1883`.
+fn parse_broker(broker: &str) -> Result<(&str, u16)> {
+    let (host, port) = match broker.strip_prefix('[') {
+        Some(bracketed) => {
+            let (host, rest) = bracketed
+                .split_once(']')
+                .ok_or_else(|| anyhow!("Missing ] in mqtt_broker {broker}"))?;
+            match rest {
+                "" => (host, None),
+                _ => match rest.strip_prefix(':') {
+                    Some(port) => (host, Some(port)),
+                    None => return Err(anyhow!("Unexpected {rest} in mqtt_broker {broker}")),
+                },
+            }
+        }
+        // More than one colon is an IPv6 address without a port
+        None if broker.matches(':').count() > 1 => (broker, None),
+        None => match broker.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (broker, None),
+        },
+    };
+    if host.is_empty() {
+        return Err(anyhow!("Missing host in mqtt_broker {broker}"));
+    }
+    let port = match port {
+        Some(port) => port
+            .parse()
+            .map_err(|e| anyhow!("Invalid port in mqtt_broker {broker}: {e}"))?,
+        None => DEFAULT_MQTT_PORT,
+    };
+    Ok((host, port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn broker_host_and_port() {
+        assert_eq!(
+            parse_broker("localhost").unwrap(),
+            ("localhost", DEFAULT_MQTT_PORT)
+        );
+        assert_eq!(parse_broker("localhost:1884").unwrap(), ("localhost", 1884));
+        assert_eq!(parse_broker("10.0.0.1:1884").unwrap(), ("10.0.0.1", 1884));
+    }
+
+    #[test]
+    fn broker_ipv6() {
+        assert_eq!(parse_broker("[::1]:1884").unwrap(), ("::1", 1884));
+        assert_eq!(parse_broker("[::1]").unwrap(), ("::1", DEFAULT_MQTT_PORT));
+        assert_eq!(parse_broker("::1").unwrap(), ("::1", DEFAULT_MQTT_PORT));
+        assert_eq!(
+            parse_broker("fe80::1:2").unwrap(),
+            ("fe80::1:2", DEFAULT_MQTT_PORT)
+        );
+    }
+
+    #[test]
+    fn broker_invalid() {
+        assert!(parse_broker("localhost:port").is_err());
+        assert!(parse_broker("[::1").is_err());
+        assert!(parse_broker("[::1]1883").is_err());
+        assert!(parse_broker(":1883").is_err());
+    }
+}
@@ -0,0 +1,71 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use log::{error, info};
+
+use crate::sinks::Sink;
+use crate::IterationResult;
+
+/// Publishes every iteration result as JSON to a NATS subject
+pub(crate) struct NatsSink {
+    url: String,
+    subject: String,
+    /// Connected on the first iteration. The client reconnects by itself if the connection is
+    /// lost later on.
+    client: Option<async_nats::Client>,
+}
+
+impl NatsSink {
+    pub(crate) fn new(url: &str, subject: &str) -> Self {
+        Self {
+            url: url.into(),
+            subject: subject.into(),
+            client: None,
+        }
+    }
+
+    async fn client(&mut self) -> Result<&async_nats::Client> {
+        let client = match self.client.take() {
+            Some(client) => client,
+            None => {
+                let client = async_nats::connect(&self.url).await?;
+                info!("Connected to NATS at {}", self.url);
+                client
+            }
+        };
+        Ok(self.client.insert(client))
+    }
+}
+
+#[async_trait]
+impl Sink for NatsSink {
+    fn name(&self) -> &'static str {
+        "NATS"
+    }
+
+    /// Publish the iteration result as JSON to the configured subject
+    async fn record(&mut self, iteration_res: &IterationResult) -> Result<()> {
+        let payload = serde_json::to_vec(iteration_res)?;
+        let subject = self.subject.clone();
+        let res = match self.client().await {
+            Ok(client) => client
+                .publish(subject, payload.into())
+                .await
+                .map_err(Into::into),
+            Err(e) => Err(e),
+        };
+        if let Err(e) = res {
+            error!("Failed to publish to NATS: {e}");
+        }
+        Ok(())
+    }
+
+    /// Wait until the published messages were sent to the server
+    async fn flush(&mut self) -> Result<()> {
+        if let Some(client) = &self.client {
+            if let Err(e) = client.flush().await {
+                error!("Failed to flush the NATS client: {e}");
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use log::error;
+use opentelemetry::metrics::{Counter, Histogram, MeterProvider, Unit};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::metrics::SdkMeterProvider;
+use opentelemetry_sdk::runtime;
+
+use crate::sinks::Sink;
+use crate::IterationResult;
+
+/// How often the collected metrics are exported in the background, in addition to the export after
+/// every iteration
+const EXPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Pushes the payment durations and outcomes to an OpenTelemetry collector over OTLP
+pub(crate) struct OtlpSink {
+    provider: SdkMeterProvider,
+    payment_duration: Histogram<f64>,
+    payment_total: Counter<u64>,
+}
+
+impl OtlpSink {
+    pub(crate) fn new(endpoint: &str) -> Result<Self> {
+        let provider = opentelemetry_otlp::new_pipeline()
+            .metrics(runtime::Tokio)
+            .with_exporter(
+                opentelemetry_otlp::new_exporter()
+                    .tonic()
+                    .with_endpoint(endpoint),
+            )
+            .with_period(EXPORT_INTERVAL)
+            .build()?;
+
+        let meter = provider.meter("sdk_pulse");
+        let payment_duration = meter
+            .f64_histogram("sdk_pulse.payment.duration")
+            .with_description("Duration of the successful payments")
+            .with_unit(Unit::new("s"))
+            .init();
+        let payment_total = meter
+            .u64_counter("sdk_pulse.payment.total")
+            .with_description("Number of attempted payments")
+            .init();
+
+        Ok(Self {
+            provider,
+            payment_duration,
+            payment_total,
+        })
+    }
+
+    /// Export what was recorded so far. Blocks until done, which is fine on the sink writer's own
+    /// thread.
+    fn export(&self) -> Result<()> {
+        self.provider.force_flush()?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Sink for OtlpSink {
+    fn name(&self) -> &'static str {
+        "OTLP"
+    }
+
+    /// Record the scenarios of the iteration and export them right away, instead of waiting for
+    /// the periodic export
+    async fn record(&mut self, iteration_res: &IterationResult) -> Result<()> {
+        for scenario in &iteration_res.scenarios {
+            let res = &scenario.measurement;
+            let scenario_attr = KeyValue::new("scenario", scenario.name.clone());
+            if let Some(duration) = res.duration {
+                self.payment_duration
+                    .record(duration.as_secs_f64(), std::slice::from_ref(&scenario_attr));
+            }
+            self.payment_total.add(
+                1,
+                &[scenario_attr, KeyValue::new("status", res.outcome.as_str())],
+            );
+        }
+
+        if let Err(e) = self.export() {
+            error!("Failed to export metrics over OTLP: {e}");
+        }
+        Ok(())
+    }
+
+    /// Export anything still pending and stop the background exports
+    async fn flush(&mut self) -> Result<()> {
+        self.export()?;
+        self.provider.shutdown()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,67 @@
+use anyhow::Result;
+use breez_sdk_core::{BreezServices, ListPaymentsRequest, PaymentDetails, PaymentStatus};
+use log::{info, warn};
+
+use crate::IterationResult;
+
+/// Payments which the scenarios recorded as successful, to check at the end of the run that the
+/// sender's payment history agrees
+#[derive(Default)]
+pub(crate) struct Reconciliation {
+    /// Scenario name and label of every successful labeled payment
+    payments: Vec<(String, String)>,
+    /// Start timestamp of the first recorded iteration
+    from_ts: Option<u64>,
+}
+
+impl Reconciliation {
+    pub(crate) fn record(&mut self, iteration_res: &IterationResult) {
+        self.from_ts.get_or_insert(iteration_res.start_ts);
+        for scenario in &iteration_res.scenarios {
+            for measurement in scenario.samples() {
+                if !measurement.is_ok() {
+                    continue;
+                }
+                if let Some(label) = &measurement.payment_label {
+                    self.payments.push((scenario.name.clone(), label.clone()));
+                }
+            }
+        }
+    }
+
+    /// Look up every recorded payment in the payment history of the sender `sdk`, logging a
+    /// warning for each one which is missing or not complete there
+    pub(crate) async fn check(&self, sdk: &BreezServices) -> Result<()> {
+        let Some(from_ts) = self.from_ts else {
+            return Ok(());
+        };
+        let node_payments = sdk
+            .list_payments(ListPaymentsRequest {
+                from_timestamp: Some(from_ts as i64),
+                include_failures: Some(true),
+                ..Default::default()
+            })
+            .await?;
+
+        let mut discrepancies = 0;
+        for (scenario, label) in &self.payments {
+            let node_payment = node_payments.iter().find(|payment| match &payment.details {
+                PaymentDetails::Ln { data } => data.label == *label,
+                _ => false,
+            });
+            let problem = match node_payment.map(|payment| &payment.status) {
+                Some(PaymentStatus::Complete) => continue,
+                Some(PaymentStatus::Pending) => "is still pending in the node's history",
+                Some(PaymentStatus::Failed) => "failed according to the node's history",
+                None => "is missing from the node's history",
+            };
+            warn!("{scenario} payment {label} was recorded as successful, but {problem}");
+            discrepancies += 1;
+        }
+        info!(
+            "Reconciled {} recorded payments with the node's history, {discrepancies} discrepancies",
+            self.payments.len()
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,69 @@
+use crate::stats::Stats;
+use crate::IterationResult;
+
+const HEADER: [&str; 7] = [
+    "scenario",
+    "duration",
+    "status",
+    "success rate",
+    "mean",
+    "p50",
+    "p90",
+];
+
+/// Print an aligned table with the results of the last iteration and the stats collected so far
+pub(crate) fn print_summary_table(last_iteration_res: &IterationResult, stats: &Stats) {
+    let rows: Vec<[String; 7]> = last_iteration_res
+        .scenarios
+        .iter()
+        .map(|scenario| {
+            let res = &scenario.measurement;
+            let scenario_stats = stats.scenario(&scenario.name);
+            [
+                scenario.name.clone(),
+                fmt_ms(res.duration_ms()),
+                res.status.clone(),
+                scenario_stats
+                    .and_then(|s| {
+                        let rate = s.success_rate()?;
+                        Some(format!("{rate:.1}% ({}/{})", s.successes, s.total))
+                    })
+                    .unwrap_or("-".into()),
+                fmt_ms(
+                    scenario_stats
+                        .and_then(|s| s.mean_ms())
+                        .map(|mean| mean.round() as u64),
+                ),
+                fmt_ms(scenario_stats.and_then(|s| s.percentile_ms(50.0))),
+                fmt_ms(scenario_stats.and_then(|s| s.percentile_ms(90.0))),
+            ]
+        })
+        .collect();
+
+    let mut widths = HEADER.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let header = HEADER.map(String::from);
+    println!("{}", fmt_row(&header, &widths));
+    println!("{}", widths.map(|width| "-".repeat(width)).join("-+-"));
+    for row in &rows {
+        println!("{}", fmt_row(row, &widths));
+    }
+}
+
+fn fmt_row(cells: &[String; 7], widths: &[usize; 7]) -> String {
+    cells
+        .iter()
+        .zip(widths)
+        .map(|(cell, width)| format!("{cell:<width$}"))
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+fn fmt_ms(value: Option<u64>) -> String {
+    value.map(|ms| format!("{ms}ms")).unwrap_or("-".into())
+}
@@ -0,0 +1,2045 @@
+use std::fmt;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use breez_sdk_core::*;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize, Serializer};
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::{Mutex, Semaphore};
+use uuid::Uuid;
+
+use crate::backoff::backoff_delay;
+use crate::sdk_client::SdkClient;
+
+/// Maximum delay before the first retry of a failed scenario, doubled on each further retry
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(2);
+/// Upper bound for the delay between retries
+const RETRY_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// How long to wait for the receiver to see a payment as complete
+const RECEIVE_VERIFY_TIMEOUT: Duration = Duration::from_secs(10);
+const RECEIVE_VERIFY_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// What every scenario has access to when it runs
+pub(crate) struct RunContext {
+    /// The sending node
+    pub(crate) sdk_1: Arc<dyn SdkClient>,
+    /// The receiving node
+    pub(crate) sdk_2: Arc<dyn SdkClient>,
+    /// Maximum duration of one attempt of a scenario
+    pub(crate) timeout_secs: u64,
+    /// How many times a failed scenario is attempted in total
+    pub(crate) attempts: u32,
+    /// Number of the current iteration, 0 before the first one
+    pub(crate) iteration: u64,
+    /// End of the current iteration, if `iteration_deadline_secs` is set
+    pub(crate) deadline: Option<Instant>,
+    /// Limits how many scenarios issuing a payment run at once
+    pub(crate) payment_permits: Semaphore,
+    /// Events of the sending and receiving nodes
+    pub(crate) sdk_1_events: Mutex<UnboundedReceiver<BreezEvent>>,
+    pub(crate) sdk_2_events: Mutex<UnboundedReceiver<BreezEvent>>,
+}
+
+impl RunContext {
+    /// Discard the events received so far, so they don't pile up between iterations
+    pub(crate) async fn drain_events(&self) {
+        for events in [&self.sdk_1_events, &self.sdk_2_events] {
+            let mut events = events.lock().await;
+            while events.try_recv().is_ok() {}
+        }
+    }
+
+    /// Whether an attempt of a scenario started now could time out after the iteration deadline
+    pub(crate) fn deadline_near(&self) -> bool {
+        self.latest_attempt_start()
+            .is_some_and(|latest| Instant::now() > latest)
+    }
+
+    /// Latest time an attempt can start and still time out before the iteration deadline
+    fn latest_attempt_start(&self) -> Option<Instant> {
+        self.deadline.map(|deadline| {
+            deadline
+                .checked_sub(Duration::from_secs(self.timeout_secs))
+                .unwrap_or(deadline)
+        })
+    }
+
+    /// Unique label of a payment of the scenario, like `pulse-12-gl2gl-<uuid>`, to find it in the
+    /// node's payment history
+    fn payment_label(&self, scenario: &str) -> String {
+        format!("pulse-{}-{scenario}-{}", self.iteration, Uuid::new_v4())
+    }
+
+    /// Like [RunContext::run_test], for a test sending a payment with the label it's passed. Every
+    /// attempt gets a new label, which is recorded in its result.
+    async fn run_labeled_test<F, Fut>(&self, scenario: &str, f: F) -> Measurement
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Measurement>,
+    {
+        with_retry(
+            self.attempts,
+            RETRY_BASE_DELAY,
+            RETRY_MAX_DELAY,
+            self.latest_attempt_start(),
+            || {
+                let label = self.payment_label(scenario);
+                let test = with_timeout(self.timeout_secs, f(label.clone()));
+                async move {
+                    Measurement {
+                        payment_label: Some(label),
+                        ..test.await
+                    }
+                }
+            },
+        )
+        .await
+    }
+
+    /// Run a test with the configured timeout and retries. No retry starts once the iteration
+    /// deadline is near.
+    async fn run_test<F, Fut>(&self, f: F) -> Measurement
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = Measurement>,
+    {
+        with_retry(
+            self.attempts,
+            RETRY_BASE_DELAY,
+            RETRY_MAX_DELAY,
+            self.latest_attempt_start(),
+            || with_timeout(self.timeout_secs, f()),
+        )
+        .await
+    }
+}
+
+#[async_trait]
+pub(crate) trait Scenario: Send + Sync {
+    /// Name of the scenario, used to label its results
+    fn name(&self) -> &str;
+
+    /// Whether the scenario must not run concurrently with others, for example because it uses
+    /// both nodes
+    fn exclusive(&self) -> bool {
+        false
+    }
+
+    /// Whether the scenario pays from the channels balance of the sender (sdk_1)
+    fn spends_sender_balance(&self) -> bool {
+        true
+    }
+
+    /// Whether the scenario sends or receives a payment, and so counts towards
+    /// `max_concurrent_payments`
+    fn issues_payment(&self) -> bool {
+        true
+    }
+
+    async fn run(&self, ctx: &RunContext) -> ScenarioResult;
+}
+
+/// Outcome of one scenario
+#[derive(Debug, Serialize)]
+pub(crate) struct ScenarioResult {
+    pub(crate) name: String,
+    #[serde(flatten)]
+    pub(crate) measurement: Measurement,
+    /// Every repetition of the scenario in this iteration, only set with [Aggregation::All]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) repetitions: Vec<Measurement>,
+}
+
+impl ScenarioResult {
+    pub(crate) fn new(name: impl Into<String>, measurement: Measurement) -> Self {
+        Self {
+            name: name.into(),
+            measurement,
+            repetitions: vec![],
+        }
+    }
+
+    /// Combine the repetitions of the scenario in one iteration into one result
+    pub(crate) fn aggregate(
+        name: impl Into<String>,
+        mut repetitions: Vec<Measurement>,
+        aggregation: Aggregation,
+    ) -> Self {
+        let name = name.into();
+        if repetitions.len() == 1 {
+            return Self::new(name, repetitions.remove(0));
+        }
+        let measurement = match aggregation {
+            Aggregation::Median | Aggregation::All => median(&repetitions),
+            Aggregation::Mean => mean(&repetitions),
+        };
+        if aggregation != Aggregation::All {
+            repetitions.clear();
+        }
+        Self {
+            name,
+            measurement,
+            repetitions,
+        }
+    }
+
+    /// The measurements to count in the stats: every repetition if they were kept, otherwise the
+    /// aggregated one
+    pub(crate) fn samples(&self) -> &[Measurement] {
+        if self.repetitions.is_empty() {
+            std::slice::from_ref(&self.measurement)
+        } else {
+            &self.repetitions
+        }
+    }
+}
+
+/// How the repetitions of a scenario within one iteration are combined into its result
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Aggregation {
+    /// The repetition with the median duration, counting failures as slower than any success
+    #[default]
+    Median,
+    /// The mean duration of the repetitions, or the first failure if any of them failed
+    Mean,
+    /// The median, while also keeping every repetition for the JSON outputs and the stats
+    All,
+}
+
+/// The repetition with the median duration, the slower one for an even number of repetitions.
+///
+/// Failures sort after all successes, so the result only fails if at least half of the
+/// repetitions failed, and skipped repetitions sort last.
+fn median(repetitions: &[Measurement]) -> Measurement {
+    let mut sorted: Vec<&Measurement> = repetitions.iter().collect();
+    sorted.sort_by_key(|res| (res.is_skipped(), !res.is_ok(), res.duration));
+    sorted[sorted.len() / 2].clone()
+}
+
+/// The last successful repetition, with the mean duration of all of them.
+///
+/// A failure counts as an infinitely long duration, so if any repetition failed, the first failure is
+/// the result. Skipped repetitions are not counted, unless all of them were skipped.
+fn mean(repetitions: &[Measurement]) -> Measurement {
+    let ran: Vec<&Measurement> = repetitions.iter().filter(|res| !res.is_skipped()).collect();
+    if let Some(failed) = ran.iter().find(|res| !res.is_ok()) {
+        return (*failed).clone();
+    }
+    let Some(last) = ran.last() else {
+        return repetitions[0].clone();
+    };
+    let total: Duration = ran.iter().filter_map(|res| res.duration).sum();
+    Measurement {
+        duration: Some(total / ran.len() as u32),
+        ..(*last).clone()
+    }
+}
+
+/// Result of a test
+#[derive(Clone, Debug, Serialize)]
+pub(crate) struct Measurement {
+    /// Duration of the test, only set if it succeeded or, for a payment the receiver didn't
+    /// confirm, if sending it succeeded
+    #[serde(rename = "duration_ms", serialize_with = "serialize_millis")]
+    pub(crate) duration: Option<Duration>,
+    pub(crate) status: String,
+    /// Cause of the failure, only set for failed tests
+    #[serde(rename = "failure_kind", skip_serializing_if = "Option::is_none")]
+    pub(crate) kind: Option<FailureKind>,
+    /// Variant of the SDK error the test failed with, like `ROUTE_NOT_FOUND`. Only set for tests
+    /// failed by an SDK payment error.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) error_code: Option<&'static str>,
+    /// Routing fee paid, only set for successful outgoing payments
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) fee_msat: Option<u64>,
+    /// Only set for scenarios which create an invoice before paying it
+    #[serde(
+        rename = "invoice_create_ms",
+        serialize_with = "serialize_millis",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub(crate) invoice_create: Option<Duration>,
+    /// Time from the payment call returning until the receiver saw the payment settled. Only set
+    /// for scenarios which wait for the receiver.
+    #[serde(
+        rename = "settlement_ms",
+        serialize_with = "serialize_millis",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub(crate) settlement: Option<Duration>,
+    /// Label the payment was sent with, only set for scenarios sending a labeled payment
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) payment_label: Option<String>,
+    /// LSP fee for opening a channel to receive the payment, 0 if none was needed. Only set for
+    /// scenarios which create an invoice.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) opening_fee_msat: Option<u64>,
+    pub(crate) outcome: Outcome,
+}
+
+impl Measurement {
+    /// Build result for a successful test
+    pub(crate) fn ok(start: Instant) -> Self {
+        Self {
+            duration: Some(start.elapsed()),
+            status: "Ok".into(),
+            kind: None,
+            error_code: None,
+            fee_msat: None,
+            invoice_create: None,
+            settlement: None,
+            opening_fee_msat: None,
+            payment_label: None,
+            outcome: Outcome::Ok,
+        }
+    }
+
+    /// Build result for a failed test
+    pub(crate) fn err(kind: FailureKind, msg: &str) -> Self {
+        error!("{msg}");
+        Self {
+            duration: None,
+            status: msg.to_string(),
+            kind: Some(kind),
+            error_code: None,
+            fee_msat: None,
+            invoice_create: None,
+            settlement: None,
+            opening_fee_msat: None,
+            payment_label: None,
+            outcome: Outcome::Failed,
+        }
+    }
+
+    /// Build result for a test failed by an SDK error, recording its [ErrorCode]
+    fn sdk_err<E>(err: &E, msg: &str) -> Self
+    where
+        E: ErrorCode,
+        for<'a> &'a E: Into<FailureKind>,
+    {
+        Self {
+            error_code: Some(err.error_code()),
+            ..Self::err(err.into(), msg)
+        }
+    }
+
+    /// Build result for a test which could not run, for example because the node doesn't support it
+    pub(crate) fn skipped(reason: &str) -> Self {
+        info!("Skipped: {reason}");
+        Self {
+            duration: None,
+            status: format!("Skipped: {reason}"),
+            kind: None,
+            error_code: None,
+            fee_msat: None,
+            invoice_create: None,
+            settlement: None,
+            opening_fee_msat: None,
+            payment_label: None,
+            outcome: Outcome::Skipped,
+        }
+    }
+
+    pub(crate) fn is_ok(&self) -> bool {
+        self.outcome == Outcome::Ok
+    }
+
+    pub(crate) fn is_failed(&self) -> bool {
+        self.outcome == Outcome::Failed
+    }
+
+    /// Whether the test failed in a way that running it again could fix. A payment that was sent
+    /// but not seen by the receiver isn't, retrying it would only pay another invoice.
+    fn is_retryable(&self) -> bool {
+        self.is_failed() && self.kind != Some(FailureKind::ReceiveUnverified)
+    }
+
+    pub(crate) fn is_skipped(&self) -> bool {
+        self.outcome == Outcome::Skipped
+    }
+
+    pub(crate) fn duration_ms(&self) -> Option<u64> {
+        self.duration.map(|d| d.as_millis() as u64)
+    }
+
+    pub(crate) fn invoice_create_ms(&self) -> Option<u64> {
+        self.invoice_create.map(|d| d.as_millis() as u64)
+    }
+
+    pub(crate) fn settlement_ms(&self) -> Option<u64> {
+        self.settlement.map(|d| d.as_millis() as u64)
+    }
+}
+
+/// Whether a test succeeded, failed or could not run at all, which is neither a success nor a
+/// failure
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Outcome {
+    Ok,
+    Failed,
+    Skipped,
+}
+
+impl Outcome {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::Ok => "ok",
+            Outcome::Failed => "failed",
+            Outcome::Skipped => "skipped",
+        }
+    }
+}
+
+/// Serialize a duration as whole milliseconds
+fn serialize_millis<S: Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    duration.map(|d| d.as_millis() as u64).serialize(serializer)
+}
+
+/// Cause of a failed test, so failures can be aggregated
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum FailureKind {
+    Timeout,
+    NoRoute,
+    InsufficientBalance,
+    /// The LNURL service returned an error
+    LnurlEndpoint,
+    /// The LN Address or LNURL could not be parsed
+    ParseFailed,
+    /// The amount is outside of what the LNURL service accepts
+    AmountOutOfRange,
+    /// The receiver doesn't have enough inbound liquidity for the amount
+    InsufficientInbound,
+    /// An SDK lost its connection and couldn't be reconnected, so the test didn't run
+    Disconnected,
+    /// The payment was sent, but the receiver didn't see it settled with the full amount
+    ReceiveUnverified,
+    Other,
+}
+
+impl FailureKind {
+    /// Classify a failure for which only the error message is known
+    fn from_reason(reason: &str) -> Self {
+        let reason = reason.to_lowercase();
+        if reason.contains("insufficient") {
+            FailureKind::InsufficientBalance
+        } else if reason.contains("no route") || reason.contains("route not found") {
+            FailureKind::NoRoute
+        } else {
+            FailureKind::Other
+        }
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            FailureKind::Timeout => "timeout",
+            FailureKind::NoRoute => "no_route",
+            FailureKind::InsufficientBalance => "insufficient_balance",
+            FailureKind::LnurlEndpoint => "lnurl_endpoint",
+            FailureKind::ParseFailed => "parse_failed",
+            FailureKind::AmountOutOfRange => "amount_out_of_range",
+            FailureKind::InsufficientInbound => "insufficient_inbound",
+            FailureKind::Disconnected => "disconnected",
+            FailureKind::ReceiveUnverified => "receive_unverified",
+            FailureKind::Other => "other",
+        };
+        write!(f, "{kind}")
+    }
+}
+
+impl From<&SendPaymentError> for FailureKind {
+    fn from(err: &SendPaymentError) -> Self {
+        match err {
+            SendPaymentError::PaymentTimeout { .. } => FailureKind::Timeout,
+            SendPaymentError::RouteNotFound { .. } | SendPaymentError::RouteTooExpensive { .. } => {
+                FailureKind::NoRoute
+            }
+            err => FailureKind::from_reason(&err.to_string()),
+        }
+    }
+}
+
+impl From<&LnUrlPayError> for FailureKind {
+    fn from(err: &LnUrlPayError) -> Self {
+        match err {
+            LnUrlPayError::PaymentTimeout { .. } => FailureKind::Timeout,
+            LnUrlPayError::RouteNotFound { .. } | LnUrlPayError::RouteTooExpensive { .. } => {
+                FailureKind::NoRoute
+            }
+            LnUrlPayError::InvalidUri { .. } => FailureKind::ParseFailed,
+            err => FailureKind::from_reason(&err.to_string()),
+        }
+    }
+}
+
+impl From<&LnUrlAuthError> for FailureKind {
+    fn from(err: &LnUrlAuthError) -> Self {
+        match err {
+            LnUrlAuthError::InvalidUri { .. } => FailureKind::ParseFailed,
+            err => FailureKind::from_reason(&err.to_string()),
+        }
+    }
+}
+
+/// Machine-readable code of an SDK error, named after its variant, so failures can be grouped
+/// without parsing the error messages
+trait ErrorCode {
+    fn error_code(&self) -> &'static str;
+}
+
+impl ErrorCode for SendPaymentError {
+    fn error_code(&self) -> &'static str {
+        match self {
+            SendPaymentError::AlreadyPaid => "ALREADY_PAID",
+            SendPaymentError::Generic { .. } => "GENERIC",
+            SendPaymentError::InvalidAmount { .. } => "INVALID_AMOUNT",
+            SendPaymentError::InvalidInvoice { .. } => "INVALID_INVOICE",
+            SendPaymentError::InvalidNetwork { .. } => "INVALID_NETWORK",
+            SendPaymentError::InvoiceExpired { .. } => "INVOICE_EXPIRED",
+            SendPaymentError::PaymentFailed { .. } => "PAYMENT_FAILED",
+            SendPaymentError::PaymentTimeout { .. } => "PAYMENT_TIMEOUT",
+            SendPaymentError::RouteNotFound { .. } => "ROUTE_NOT_FOUND",
+            SendPaymentError::RouteTooExpensive { .. } => "ROUTE_TOO_EXPENSIVE",
+            SendPaymentError::ServiceConnectivity { .. } => "SERVICE_CONNECTIVITY",
+        }
+    }
+}
+
+impl ErrorCode for LnUrlPayError {
+    fn error_code(&self) -> &'static str {
+        match self {
+            LnUrlPayError::AlreadyPaid => "ALREADY_PAID",
+            LnUrlPayError::Generic { .. } => "GENERIC",
+            LnUrlPayError::InvalidAmount { .. } => "INVALID_AMOUNT",
+            LnUrlPayError::InvalidInvoice { .. } => "INVALID_INVOICE",
+            LnUrlPayError::InvalidNetwork { .. } => "INVALID_NETWORK",
+            LnUrlPayError::InvalidUri { .. } => "INVALID_URI",
+            LnUrlPayError::InvoiceExpired { .. } => "INVOICE_EXPIRED",
+            LnUrlPayError::PaymentFailed { .. } => "PAYMENT_FAILED",
+            LnUrlPayError::PaymentTimeout { .. } => "PAYMENT_TIMEOUT",
+            LnUrlPayError::RouteNotFound { .. } => "ROUTE_NOT_FOUND",
+            LnUrlPayError::RouteTooExpensive { .. } => "ROUTE_TOO_EXPENSIVE",
+            LnUrlPayError::ServiceConnectivity { .. } => "SERVICE_CONNECTIVITY",
+        }
+    }
+}
+
+impl ErrorCode for LnUrlAuthError {
+    fn error_code(&self) -> &'static str {
+        match self {
+            LnUrlAuthError::Generic { .. } => "GENERIC",
+            LnUrlAuthError::InvalidUri { .. } => "INVALID_URI",
+            LnUrlAuthError::ServiceConnectivity { .. } => "SERVICE_CONNECTIVITY",
+        }
+    }
+}
+
+/// Run a test, marking it as failed if it doesn't complete within `timeout_secs`
+async fn with_timeout(timeout_secs: u64, test: impl Future<Output = Measurement>) -> Measurement {
+    match tokio::time::timeout(Duration::from_secs(timeout_secs), test).await {
+        Ok(res) => res,
+        Err(_) => Measurement::err(FailureKind::Timeout, "timeout"),
+    }
+}
+
+/// Run a test up to `attempts` times, until it succeeds or fails in a way retrying can't fix.
+///
+/// The delay between attempts is a [backoff_delay] starting at `base_delay`, up to `max_delay`. If
+/// more than one attempt was made, their number is appended to the result status. No retry starts
+/// after `latest_retry`.
+async fn with_retry<F, Fut>(
+    attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    latest_retry: Option<Instant>,
+    f: F,
+) -> Measurement
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Measurement>,
+{
+    let attempts = attempts.max(1);
+    let mut attempt = 1;
+    loop {
+        let mut res = f().await;
+        if !res.is_retryable() || attempt == attempts {
+            if attempt > 1 {
+                res.status = format!("{} (attempts: {attempt})", res.status);
+            }
+            return res;
+        }
+
+        let delay = backoff_delay(attempt - 1, base_delay, max_delay);
+        if latest_retry.is_some_and(|latest| Instant::now() + delay > latest) {
+            warn!("Attempt {attempt}/{attempts} failed, not retrying as the iteration deadline is near");
+            res.status = format!(
+                "{} (attempts: {attempt}, stopped by the iteration deadline)",
+                res.status
+            );
+            return res;
+        }
+        info!("Attempt {attempt}/{attempts} failed, retrying in {delay:?}");
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Settings of the invoices created by the receiving side of a scenario
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct InvoiceOptions {
+    pub(crate) expiry_secs: u32,
+    /// Min final CLTV expiry delta, if not the SDK default
+    pub(crate) cltv: Option<u32>,
+    /// If true, fetch the LSP's opening fee params for the amount and expiry and create the
+    /// invoice with them, instead of letting the SDK pick them
+    pub(crate) fetch_opening_fee_params: bool,
+    /// If true, the invoice only contains the hash of the description instead of the description
+    /// itself, like the invoices of LNURL-pay services
+    pub(crate) use_description_hash: bool,
+}
+
+impl InvoiceOptions {
+    /// Create an invoice on `sdk`
+    async fn create_invoice(
+        &self,
+        sdk: &dyn SdkClient,
+        amount_msat: u64,
+        description: &str,
+    ) -> Result<ReceivePaymentResponse, ReceivePaymentError> {
+        let opening_fee_params = if self.fetch_opening_fee_params {
+            self.opening_fee_params(sdk, amount_msat).await
+        } else {
+            None
+        };
+        sdk.receive_payment(ReceivePaymentRequest {
+            amount_msat,
+            description: description.into(),
+            preimage: None,
+            opening_fee_params,
+            use_description_hash: self.use_description_hash.then_some(true),
+            expiry: Some(self.expiry_secs),
+            cltv: self.cltv,
+        })
+        .await
+    }
+
+    /// The LSP's opening fee params for the amount, or None to let the SDK pick them if they
+    /// couldn't be fetched
+    async fn opening_fee_params(
+        &self,
+        sdk: &dyn SdkClient,
+        amount_msat: u64,
+    ) -> Option<OpeningFeeParams> {
+        match sdk
+            .open_channel_fee(OpenChannelFeeRequest {
+                amount_msat: Some(amount_msat),
+                expiry: Some(self.expiry_secs),
+            })
+            .await
+        {
+            Ok(res) => {
+                info!(
+                    "Opening fee for {amount_msat} msat: {:?} msat, with params {:?}",
+                    res.fee_msat, res.fee_params
+                );
+                Some(res.fee_params)
+            }
+            Err(e) => {
+                error!("Failed to fetch the opening fee params: {e}");
+                None
+            }
+        }
+    }
+}
+
+/// Pay an LN Address from sdk_1
+pub(crate) struct Gl2LnAddress {
+    pub(crate) name: String,
+    pub(crate) address: String,
+    pub(crate) amount_msat: u64,
+    /// LNURL-pay comment sent with the payment
+    pub(crate) comment: String,
+    /// If true, an amount outside of the endpoint's sendable range is changed to the nearest bound
+    /// instead of failing the scenario
+    pub(crate) clamp_amount: bool,
+}
+
+#[async_trait]
+impl Scenario for Gl2LnAddress {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn run(&self, ctx: &RunContext) -> ScenarioResult {
+        let measurement = ctx
+            .run_labeled_test(&self.name, |label| {
+                pay_gl_2_ln_address(
+                    ctx.sdk_1.clone(),
+                    &self.address,
+                    self.amount_msat,
+                    &self.comment,
+                    self.clamp_amount,
+                    label,
+                )
+            })
+            .await;
+        ScenarioResult::new(&self.name, measurement)
+    }
+}
+
+/// Pay an invoice of sdk_2 from sdk_1
+pub(crate) struct Gl2Gl {
+    pub(crate) amount_msat: u64,
+    /// Description of the invoice
+    pub(crate) description: String,
+    pub(crate) invoice: InvoiceOptions,
+    /// If true and sdk_2 lacks the inbound liquidity for the amount, fail the scenario instead of
+    /// creating the invoice with the LSP's opening fee params, so a channel is opened
+    pub(crate) fail_if_no_inbound: bool,
+}
+
+#[async_trait]
+impl Scenario for Gl2Gl {
+    fn name(&self) -> &str {
+        "gl2gl"
+    }
+
+    fn exclusive(&self) -> bool {
+        true
+    }
+
+    async fn run(&self, ctx: &RunContext) -> ScenarioResult {
+        let measurement = ctx
+            .run_labeled_test(self.name(), |label| {
+                pay_gl_2_gl(
+                    ctx.sdk_1.clone(),
+                    ctx.sdk_2.clone(),
+                    &ctx.sdk_2_events,
+                    self.amount_msat,
+                    &self.description,
+                    self.invoice,
+                    self.fail_if_no_inbound,
+                    label,
+                )
+            })
+            .await;
+        ScenarioResult::new(self.name(), measurement)
+    }
+}
+
+/// Pay an invoice of sdk_1 from sdk_1 itself, which isolates the LSP from any other wallet
+pub(crate) struct SelfPayment {
+    pub(crate) amount_msat: u64,
+    /// Description of the invoice
+    pub(crate) description: String,
+    pub(crate) invoice: InvoiceOptions,
+}
+
+#[async_trait]
+impl Scenario for SelfPayment {
+    fn name(&self) -> &str {
+        "self"
+    }
+
+    async fn run(&self, ctx: &RunContext) -> ScenarioResult {
+        let measurement = ctx
+            .run_labeled_test(self.name(), |label| {
+                pay_self(
+                    ctx.sdk_1.clone(),
+                    self.amount_msat,
+                    &self.description,
+                    self.invoice,
+                    label,
+                )
+            })
+            .await;
+        ScenarioResult::new(self.name(), measurement)
+    }
+}
+
+/// Create a swap-in address on sdk_2 and fetch the swap info, without sending any on-chain funds
+pub(crate) struct SwapIn;
+
+#[async_trait]
+impl Scenario for SwapIn {
+    fn name(&self) -> &str {
+        "swapin"
+    }
+
+    fn spends_sender_balance(&self) -> bool {
+        false
+    }
+
+    fn issues_payment(&self) -> bool {
+        false
+    }
+
+    async fn run(&self, ctx: &RunContext) -> ScenarioResult {
+        let measurement = ctx.run_test(|| create_swap_in(ctx.sdk_2.clone())).await;
+        ScenarioResult::new(self.name(), measurement)
+    }
+}
+
+/// Send funds on-chain from sdk_1 through a reverse swap
+pub(crate) struct Gl2Onchain {
+    pub(crate) address: String,
+    pub(crate) amount_sat: u64,
+}
+
+#[async_trait]
+impl Scenario for Gl2Onchain {
+    fn name(&self) -> &str {
+        "gl2onchain"
+    }
+
+    async fn run(&self, ctx: &RunContext) -> ScenarioResult {
+        let measurement = ctx
+            .run_test(|| pay_gl_2_onchain(ctx.sdk_1.clone(), &self.address, self.amount_sat))
+            .await;
+        ScenarioResult::new(self.name(), measurement)
+    }
+}
+
+/// Receive from an LNURL-withdraw service on sdk_1
+pub(crate) struct LnUrlWithdraw2Gl {
+    pub(crate) lnurl: String,
+    pub(crate) amount_msat: u64,
+    /// Description of the withdraw invoice
+    pub(crate) description: String,
+}
+
+#[async_trait]
+impl Scenario for LnUrlWithdraw2Gl {
+    fn name(&self) -> &str {
+        "lnurlw2gl"
+    }
+
+    fn spends_sender_balance(&self) -> bool {
+        false
+    }
+
+    async fn run(&self, ctx: &RunContext) -> ScenarioResult {
+        let measurement = ctx
+            .run_test(|| {
+                withdraw_from_lnurl(
+                    ctx.sdk_1.clone(),
+                    &self.lnurl,
+                    self.amount_msat,
+                    &self.description,
+                )
+            })
+            .await;
+        ScenarioResult::new(self.name(), measurement)
+    }
+}
+
+/// Log in to an LNURL-auth service with sdk_1
+pub(crate) struct LnUrlAuth {
+    pub(crate) lnurl: String,
+}
+
+#[async_trait]
+impl Scenario for LnUrlAuth {
+    fn name(&self) -> &str {
+        "lnurlauth"
+    }
+
+    fn spends_sender_balance(&self) -> bool {
+        false
+    }
+
+    fn issues_payment(&self) -> bool {
+        false
+    }
+
+    async fn run(&self, ctx: &RunContext) -> ScenarioResult {
+        let measurement = ctx
+            .run_test(|| lnurl_auth_scenario(ctx.sdk_1.clone(), &self.lnurl))
+            .await;
+        ScenarioResult::new(self.name(), measurement)
+    }
+}
+
+/// Send a keysend payment from sdk_1
+pub(crate) struct Gl2Keysend {
+    pub(crate) node_id: String,
+    pub(crate) amount_msat: u64,
+}
+
+#[async_trait]
+impl Scenario for Gl2Keysend {
+    fn name(&self) -> &str {
+        "gl2keysend"
+    }
+
+    async fn run(&self, ctx: &RunContext) -> ScenarioResult {
+        let measurement = ctx
+            .run_labeled_test(self.name(), |label| {
+                pay_gl_2_keysend(ctx.sdk_1.clone(), &self.node_id, self.amount_msat, label)
+            })
+            .await;
+        ScenarioResult::new(self.name(), measurement)
+    }
+}
+
+/// Resolve the LN Address and pay it with [pay_lnurl_pay]
+async fn pay_gl_2_ln_address(
+    sdk_sender: Arc<dyn SdkClient>,
+    ln_address: &str,
+    amount_msat: u64,
+    comment: &str,
+    clamp_amount: bool,
+    label: String,
+) -> Measurement {
+    match parse(ln_address).await {
+        Ok(InputType::LnUrlPay { data }) => {
+            pay_lnurl_pay(
+                sdk_sender.as_ref(),
+                data,
+                ln_address,
+                amount_msat,
+                comment,
+                clamp_amount,
+                label,
+            )
+            .await
+        }
+        Ok(InputType::LnUrlError { data }) => Measurement::err(
+            FailureKind::LnurlEndpoint,
+            &format!("LNURL error: {}", data.reason),
+        ),
+        Ok(input_type) => Measurement::err(
+            FailureKind::ParseFailed,
+            &format!("Expected LnUrlPay, got {}", input_type_name(&input_type)),
+        ),
+        Err(e) => Measurement::err(
+            FailureKind::ParseFailed,
+            &format!("Failed to parse LN Address: {e}"),
+        ),
+    }
+}
+
+/// Pay the resolved LNURL-pay endpoint of the LN Address, checking the amount against its sendable
+/// range first.
+///
+/// An out of range amount fails the test, unless `clamp_amount` is set.
+async fn pay_lnurl_pay(
+    sdk_sender: &dyn SdkClient,
+    data: LnUrlPayRequestData,
+    ln_address: &str,
+    amount_msat: u64,
+    comment: &str,
+    clamp_amount: bool,
+    label: String,
+) -> Measurement {
+    let (min_msat, max_msat) = (data.min_sendable, data.max_sendable);
+    let amount_msat = if (min_msat..=max_msat).contains(&amount_msat) {
+        amount_msat
+    } else if clamp_amount && min_msat <= max_msat {
+        let clamped_msat = amount_msat.clamp(min_msat, max_msat);
+        info!(
+            "Amount {amount_msat} msat out of range for {ln_address}, sending {clamped_msat} msat"
+        );
+        clamped_msat
+    } else {
+        return Measurement::err(
+            FailureKind::AmountOutOfRange,
+            &format!(
+                "Amount {amount_msat} msat outside of the sendable range {min_msat}-{max_msat} msat"
+            ),
+        );
+    };
+
+    let comment = truncate_comment(comment, data.comment_allowed);
+
+    let ts_start = Instant::now();
+    match sdk_sender
+        .lnurl_pay(LnUrlPayRequest {
+            data,
+            amount_msat,
+            comment: Some(comment),
+            payment_label: Some(label),
+        })
+        .await
+    {
+        // LNURL-pay success case
+        Ok(LnUrlPayResult::EndpointSuccess { data }) => Measurement {
+            fee_msat: Some(data.payment.fee_msat),
+            ..Measurement::ok(ts_start)
+        },
+
+        // LNURL-pay failure cases
+        Ok(LnUrlPayResult::EndpointError { data }) => {
+            Measurement::err(FailureKind::LnurlEndpoint, &data.reason)
+        }
+        Ok(LnUrlPayResult::PayError { data }) => {
+            Measurement::err(FailureKind::from_reason(&data.reason), &data.reason)
+        }
+        Err(e) => Measurement::sdk_err(&e, &e.to_string()),
+    }
+}
+
+/// Truncate the comment to the `comment_allowed` characters advertised by the LNURL-pay endpoint.
+/// A limit of 0 means the endpoint doesn't say, in which case the comment is sent as is.
+fn truncate_comment(comment: &str, comment_allowed: u16) -> String {
+    let comment_allowed = comment_allowed as usize;
+    if comment_allowed == 0 || comment.chars().count() <= comment_allowed {
+        return comment.into();
+    }
+    info!(
+        "Truncating LNURL-pay comment to the {comment_allowed} characters allowed by the endpoint"
+    );
+    comment.chars().take(comment_allowed).collect()
+}
+
+/// Pay an invoice of the receiver, timing the invoice creation and the payment separately.
+///
+/// After the payment, this also measures how long it took the receiver to see it settled. If the
+/// receiver doesn't see it settled with the full amount, the test fails as
+/// [FailureKind::ReceiveUnverified], still with the duration and fee of the sent payment.
+///
+/// If the receiver lacks the inbound liquidity for the amount, the invoice is created with the LSP's
+/// opening fee params, so paying it opens a channel. With `fail_if_no_inbound`, this fails with the
+/// `insufficient_inbound` status before creating the invoice instead.
+#[allow(clippy::too_many_arguments)]
+async fn pay_gl_2_gl(
+    sdk_sender: Arc<dyn SdkClient>,
+    sdk_receiver: Arc<dyn SdkClient>,
+    receiver_events: &Mutex<UnboundedReceiver<BreezEvent>>,
+    amount_msat: u64,
+    description: &str,
+    mut invoice: InvoiceOptions,
+    fail_if_no_inbound: bool,
+    label: String,
+) -> Measurement {
+    match sdk_receiver.node_info() {
+        Ok(node_info) if node_info.inbound_liquidity_msats < amount_msat => {
+            let msg = format!(
+                "[sdk-rx] Inbound liquidity of {} msat is below the amount of {amount_msat} msat",
+                node_info.inbound_liquidity_msats
+            );
+            if fail_if_no_inbound {
+                return Measurement {
+                    status: "insufficient_inbound".into(),
+                    ..Measurement::err(FailureKind::InsufficientInbound, &msg)
+                };
+            }
+            info!("{msg}, creating the invoice with opening fee params");
+            invoice.fetch_opening_fee_params = true;
+        }
+        Ok(_) => {}
+        // Not knowing the liquidity shouldn't fail the scenario, the payment itself may still work
+        Err(e) => warn!("[sdk-rx] Failed to check the inbound liquidity: {e}"),
+    }
+
+    // Hold the lock for the whole test, so no other test consumes the settlement event
+    let mut receiver_events = receiver_events.lock().await;
+    while receiver_events.try_recv().is_ok() {}
+
+    let ts_invoice_start = Instant::now();
+
+    info!("[sdk-rx] Creating invoice");
+    match invoice
+        .create_invoice(sdk_receiver.as_ref(), amount_msat, description)
+        .await
+    {
+        Ok(recv_payment) => {
+            let invoice_create = Some(ts_invoice_start.elapsed());
+            let opening_fee_msat = Some(recv_payment.opening_fee_msat.unwrap_or_default());
+            let ts_start = Instant::now();
+
+            info!("[sdk-tx] Paying invoice");
+            let payment = match sdk_sender
+                .send_payment(SendPaymentRequest {
+                    bolt11: recv_payment.ln_invoice.bolt11,
+                    amount_msat: None, // The invoice already specifies the amount
+                    label: Some(label),
+                })
+                .await
+            {
+                Ok(res) => {
+                    let measurement = Measurement {
+                        fee_msat: Some(res.payment.fee_msat),
+                        ..Measurement::ok(ts_start)
+                    };
+                    let payment_hash = recv_payment.ln_invoice.payment_hash;
+                    // If the payment opened a channel, the LSP kept the opening fee
+                    let expected_msat = amount_msat
+                        .saturating_sub(recv_payment.opening_fee_msat.unwrap_or_default());
+                    let verified = match wait_for_invoice_paid(&mut receiver_events, &payment_hash)
+                        .await
+                    {
+                        Some(settlement) => {
+                            verify_received(sdk_receiver.as_ref(), &payment_hash, expected_msat)
+                                .await
+                                .map(|()| settlement)
+                        }
+                        None => {
+                            Err("Payment not settled within the verification timeout".to_string())
+                        }
+                    };
+                    match verified {
+                        Ok(settlement) => Measurement {
+                            settlement: Some(settlement),
+                            ..measurement
+                        },
+                        Err(e) => {
+                            let msg = format!("[sdk-rx] {e}");
+                            error!("{msg}");
+                            Measurement {
+                                status: msg,
+                                kind: Some(FailureKind::ReceiveUnverified),
+                                outcome: Outcome::Failed,
+                                ..measurement
+                            }
+                        }
+                    }
+                }
+                Err(e) => {
+                    Measurement::sdk_err(&e, &format!("[sdk-tx] Failed to send payment: {e}"))
+                }
+            };
+
+            Measurement {
+                invoice_create,
+                opening_fee_msat,
+                ..payment
+            }
+        }
+        Err(e) => Measurement::err(
+            FailureKind::Other,
+            &format!("[sdk-rx] Failed to create invoice: {e}"),
+        ),
+    }
+}
+
+/// Wait for the receiver's event that the invoice was paid, returning how long that took
+async fn wait_for_invoice_paid(
+    receiver_events: &mut UnboundedReceiver<BreezEvent>,
+    payment_hash: &str,
+) -> Option<Duration> {
+    let ts_start = Instant::now();
+    let invoice_paid = async {
+        while let Some(event) = receiver_events.recv().await {
+            if let BreezEvent::InvoicePaid { details } = event {
+                if details.payment_hash == payment_hash {
+                    return Some(ts_start.elapsed());
+                }
+            }
+        }
+        None
+    };
+    tokio::time::timeout(RECEIVE_VERIFY_TIMEOUT, invoice_paid)
+        .await
+        .ok()
+        .flatten()
+}
+
+/// Wait until the receiver has the payment as complete, and check that it got `amount_msat`
+async fn verify_received(
+    sdk_receiver: &dyn SdkClient,
+    payment_hash: &str,
+    amount_msat: u64,
+) -> Result<(), String> {
+    let deadline = Instant::now() + RECEIVE_VERIFY_TIMEOUT;
+    loop {
+        match sdk_receiver.payment_by_hash(payment_hash.into()).await {
+            Ok(Some(payment)) if payment.status == PaymentStatus::Complete => {
+                if payment.amount_msat != amount_msat {
+                    return Err(format!(
+                        "Received {} msat instead of {amount_msat} msat",
+                        payment.amount_msat
+                    ));
+                }
+                return Ok(());
+            }
+            Ok(_) => {}
+            Err(e) => return Err(format!("Failed to look up the received payment: {e}")),
+        }
+
+        if Instant::now() >= deadline {
+            return Err("Payment not received within the verification timeout".into());
+        }
+        tokio::time::sleep(RECEIVE_VERIFY_POLL_INTERVAL).await;
+    }
+}
+
+/// Pay an invoice of the same node, measuring from the invoice creation until the payment completed
+async fn pay_self(
+    sdk: Arc<dyn SdkClient>,
+    amount_msat: u64,
+    description: &str,
+    invoice: InvoiceOptions,
+    label: String,
+) -> Measurement {
+    let ts_start = Instant::now();
+
+    info!("[sdk] Creating invoice to self");
+    let recv_payment = match invoice
+        .create_invoice(sdk.as_ref(), amount_msat, description)
+        .await
+    {
+        Ok(recv_payment) => recv_payment,
+        Err(e) => {
+            return Measurement::err(
+                FailureKind::Other,
+                &format!("[sdk] Failed to create invoice: {e}"),
+            )
+        }
+    };
+
+    info!("[sdk] Paying own invoice");
+    match sdk
+        .send_payment(SendPaymentRequest {
+            bolt11: recv_payment.ln_invoice.bolt11,
+            amount_msat: None,
+            label: Some(label),
+        })
+        .await
+    {
+        Ok(res) => Measurement {
+            fee_msat: Some(res.payment.fee_msat),
+            opening_fee_msat: Some(recv_payment.opening_fee_msat.unwrap_or_default()),
+            ..Measurement::ok(ts_start)
+        },
+        Err(e) => Measurement::sdk_err(&e, &format!("[sdk] Failed to pay own invoice: {e}")),
+    }
+}
+
+async fn pay_gl_2_keysend(
+    sdk_sender: Arc<dyn SdkClient>,
+    node_id: &str,
+    amount_msat: u64,
+    label: String,
+) -> Measurement {
+    let ts_start = Instant::now();
+
+    info!("[sdk-tx] Sending keysend payment");
+    match sdk_sender
+        .send_spontaneous_payment(SendSpontaneousPaymentRequest {
+            node_id: node_id.into(),
+            amount_msat,
+            extra_tlvs: None,
+            label: Some(label),
+        })
+        .await
+    {
+        Ok(res) => Measurement {
+            fee_msat: Some(res.payment.fee_msat),
+            ..Measurement::ok(ts_start)
+        },
+        Err(e) => {
+            Measurement::sdk_err(&e, &format!("[sdk-tx] Failed to send keysend payment: {e}"))
+        }
+    }
+}
+
+/// Get a swap-in address and fetch the in-progress swap info.
+///
+/// If the node already has a swap in progress, no new address can be created, so the test is skipped.
+async fn create_swap_in(sdk: Arc<dyn SdkClient>) -> Measurement {
+    let ts_start = Instant::now();
+
+    info!("[sdk-rx] Creating swap-in address");
+    match sdk
+        .receive_onchain(ReceiveOnchainRequest {
+            opening_fee_params: None,
+        })
+        .await
+    {
+        Ok(swap_info) => info!("[sdk-rx] Swap-in address: {}", swap_info.bitcoin_address),
+        Err(ReceiveOnchainError::SwapInProgress { err }) => {
+            return Measurement::skipped(&format!("Swap already in progress: {err}"))
+        }
+        Err(e) => {
+            return Measurement::err(
+                FailureKind::from_reason(&e.to_string()),
+                &format!("[sdk-rx] Failed to create swap-in address: {e}"),
+            )
+        }
+    }
+
+    match sdk.in_progress_swap().await {
+        Ok(_) => Measurement::ok(ts_start),
+        Err(e) => Measurement::err(
+            FailureKind::Other,
+            &format!("[sdk-rx] Failed to fetch swap info: {e}"),
+        ),
+    }
+}
+
+/// Prepare and initiate a reverse swap to the on-chain address.
+///
+/// Confirming the claim tx takes too long to measure, so this only measures until the swap was
+/// initiated.
+async fn pay_gl_2_onchain(
+    sdk_sender: Arc<dyn SdkClient>,
+    address: &str,
+    amount_sat: u64,
+) -> Measurement {
+    let ts_start = Instant::now();
+
+    let claim_tx_feerate = match sdk_sender.recommended_fees().await {
+        Ok(fees) => fees.hour_fee as u32,
+        Err(e) => {
+            return Measurement::err(
+                FailureKind::Other,
+                &format!("[sdk-tx] Failed to get recommended fees: {e}"),
+            )
+        }
+    };
+
+    info!("[sdk-tx] Preparing reverse swap");
+    let prepare_res = match sdk_sender
+        .prepare_onchain_payment(PrepareOnchainPaymentRequest {
+            amount_sat,
+            amount_type: SwapAmountType::Send,
+            claim_tx_feerate,
+        })
+        .await
+    {
+        Ok(prepare_res) => prepare_res,
+        Err(e) => {
+            return Measurement::err(
+                FailureKind::from_reason(&e.to_string()),
+                &format!("[sdk-tx] Failed to prepare reverse swap: {e}"),
+            )
+        }
+    };
+
+    info!("[sdk-tx] Initiating reverse swap");
+    match sdk_sender
+        .pay_onchain(PayOnchainRequest {
+            recipient_address: address.into(),
+            prepare_res,
+        })
+        .await
+    {
+        Ok(res) => {
+            info!(
+                "[sdk-tx] Reverse swap initiated: {}",
+                res.reverse_swap_info.id
+            );
+            Measurement {
+                status: "Ok (initiated, not confirmed)".into(),
+                ..Measurement::ok(ts_start)
+            }
+        }
+        Err(e) => Measurement::err(
+            FailureKind::from_reason(&e.to_string()),
+            &format!("[sdk-tx] Failed to initiate reverse swap: {e}"),
+        ),
+    }
+}
+
+/// Receive a payment from an LNURL-withdraw service.
+///
+/// The SDK waits for the withdraw invoice to be paid, so this measures the time until the payment
+/// was received.
+async fn withdraw_from_lnurl(
+    sdk: Arc<dyn SdkClient>,
+    lnurl: &str,
+    amount_msat: u64,
+    description: &str,
+) -> Measurement {
+    match parse(lnurl).await {
+        Ok(InputType::LnUrlWithdraw { data }) => {
+            let ts_start = Instant::now();
+            match sdk
+                .lnurl_withdraw(LnUrlWithdrawRequest {
+                    data,
+                    amount_msat,
+                    description: Some(description.into()),
+                })
+                .await
+            {
+                Ok(LnUrlWithdrawResult::Ok { .. }) => Measurement::ok(ts_start),
+                Ok(LnUrlWithdrawResult::Timeout { .. }) => Measurement::err(
+                    FailureKind::Timeout,
+                    "Timed out waiting for the LNURL-withdraw payment",
+                ),
+                Ok(LnUrlWithdrawResult::ErrorStatus { data }) => {
+                    Measurement::err(FailureKind::LnurlEndpoint, &data.reason)
+                }
+                Err(e) => {
+                    Measurement::err(FailureKind::from_reason(&e.to_string()), &e.to_string())
+                }
+            }
+        }
+        Ok(InputType::LnUrlError { data }) => Measurement::err(
+            FailureKind::LnurlEndpoint,
+            &format!("LNURL error: {}", data.reason),
+        ),
+        Ok(input_type) => Measurement::err(
+            FailureKind::ParseFailed,
+            &format!(
+                "Expected LnUrlWithdraw, got {}",
+                input_type_name(&input_type)
+            ),
+        ),
+        Err(e) => Measurement::err(
+            FailureKind::ParseFailed,
+            &format!("Failed to parse LNURL-withdraw: {e}"),
+        ),
+    }
+}
+
+/// Log in to an LNURL-auth service, measuring the round trip of signing the challenge and
+/// sending it to the service
+async fn lnurl_auth_scenario(sdk: Arc<dyn SdkClient>, lnurl: &str) -> Measurement {
+    match parse(lnurl).await {
+        Ok(InputType::LnUrlAuth { data }) => {
+            let ts_start = Instant::now();
+            let domain = data.domain.clone();
+            match sdk.lnurl_auth(data).await {
+                Ok(LnUrlCallbackStatus::Ok) => Measurement::ok(ts_start),
+                Ok(LnUrlCallbackStatus::ErrorStatus { data }) => Measurement::err(
+                    FailureKind::LnurlEndpoint,
+                    &format!("LNURL-auth login rejected by {domain}: {}", data.reason),
+                ),
+                Err(e) => Measurement::sdk_err(&e, &format!("Failed to log in to {domain}: {e}")),
+            }
+        }
+        Ok(InputType::LnUrlError { data }) => Measurement::err(
+            FailureKind::LnurlEndpoint,
+            &format!("LNURL error: {}", data.reason),
+        ),
+        Ok(input_type) => Measurement::err(
+            FailureKind::ParseFailed,
+            &format!("Expected LnUrlAuth, got {}", input_type_name(&input_type)),
+        ),
+        Err(e) => Measurement::err(
+            FailureKind::ParseFailed,
+            &format!("Failed to parse LNURL-auth: {e}"),
+        ),
+    }
+}
+
+/// Name of the variant of the parsed input, to report what was found instead of the expected one
+pub(crate) fn input_type_name(input_type: &InputType) -> &'static str {
+    match input_type {
+        InputType::BitcoinAddress { .. } => "BitcoinAddress",
+        InputType::Bolt11 { .. } => "Bolt11",
+        InputType::NodeId { .. } => "NodeId",
+        InputType::Url { .. } => "Url",
+        InputType::LnUrlPay { .. } => "LnUrlPay",
+        InputType::LnUrlWithdraw { .. } => "LnUrlWithdraw",
+        InputType::LnUrlAuth { .. } => "LnUrlAuth",
+        InputType::LnUrlError { .. } => "LnUrlError",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::sdk_client::mock::{payment, received_invoice, MockSdkClient};
+    use crate::sinks::{csv_header, csv_record};
+    use crate::IterationResult;
+
+    const AMOUNT_MSAT: u64 = 1000;
+    const PAYMENT_HASH: &str = "hash";
+
+    /// The CSV fields of an iteration with only this scenario result, by column name
+    fn csv_fields(
+        scenario: Box<dyn Scenario>,
+        measurement: &Measurement,
+    ) -> HashMap<String, String> {
+        let res = ScenarioResult::new(scenario.name(), measurement.clone());
+        let header = csv_header(&[scenario], "USD");
+        let record = csv_record(&IterationResult::with_scenarios(vec![res]));
+        header.into_iter().zip(record).collect()
+    }
+
+    fn gl2gl() -> Box<dyn Scenario> {
+        Box::new(Gl2Gl {
+            amount_msat: AMOUNT_MSAT,
+            description: "test-gl2gl".into(),
+            invoice: invoice_options(),
+            fail_if_no_inbound: false,
+        })
+    }
+
+    fn invoice_options() -> InvoiceOptions {
+        InvoiceOptions {
+            expiry_secs: 60,
+            cltv: None,
+            fetch_opening_fee_params: false,
+            use_description_hash: false,
+        }
+    }
+
+    fn node_state(inbound_liquidity_msats: u64) -> NodeState {
+        NodeState {
+            inbound_liquidity_msats,
+            ..Default::default()
+        }
+    }
+
+    /// A sender and receiver whose payments succeed, the receiver getting `received_msat`
+    fn gl2gl_nodes(
+        inbound_liquidity_msats: u64,
+        opening_fee_msat: Option<u64>,
+        received_msat: u64,
+    ) -> (
+        MockSdkClient,
+        MockSdkClient,
+        Mutex<UnboundedReceiver<BreezEvent>>,
+    ) {
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+        let sender = MockSdkClient {
+            invoice_paid_events: Some(events_tx),
+            ..Default::default()
+        };
+        sender.send_payment.push(Ok(SendPaymentResponse {
+            payment: payment(PAYMENT_HASH, AMOUNT_MSAT, 3),
+        }));
+        let receiver = MockSdkClient::default();
+        receiver
+            .node_info
+            .push(Ok(node_state(inbound_liquidity_msats)));
+        receiver.receive_payment.push(Ok(received_invoice(
+            PAYMENT_HASH,
+            AMOUNT_MSAT,
+            opening_fee_msat,
+        )));
+        receiver
+            .payment_by_hash
+            .push(Ok(Some(payment(PAYMENT_HASH, received_msat, 0))));
+        (sender, receiver, Mutex::new(events_rx))
+    }
+
+    async fn run_gl2gl(
+        sender: MockSdkClient,
+        receiver: MockSdkClient,
+        events: &Mutex<UnboundedReceiver<BreezEvent>>,
+        fail_if_no_inbound: bool,
+    ) -> Measurement {
+        pay_gl_2_gl(
+            Arc::new(sender),
+            Arc::new(receiver),
+            events,
+            AMOUNT_MSAT,
+            "test-gl2gl",
+            invoice_options(),
+            fail_if_no_inbound,
+            "label".into(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn gl2gl_success_records_fee_and_settlement() {
+        let (sender, receiver, events) = gl2gl_nodes(1_000_000, None, AMOUNT_MSAT);
+        let measurement = run_gl2gl(sender, receiver, &events, false).await;
+
+        assert_eq!(measurement.outcome, Outcome::Ok);
+        assert_eq!(measurement.kind, None);
+        assert_eq!(measurement.error_code, None);
+        assert_eq!(measurement.fee_msat, Some(3));
+        assert_eq!(measurement.opening_fee_msat, Some(0));
+        assert!(measurement.invoice_create.is_some());
+        assert!(measurement.settlement.is_some());
+
+        let fields = csv_fields(gl2gl(), &measurement);
+        assert_eq!(fields["gl2gl_outcome"], "ok");
+        assert_eq!(fields["gl2gl_status"], "Ok");
+        assert_eq!(fields["gl2gl_failure_kind"], "");
+        assert_eq!(fields["gl2gl_error_code"], "");
+        assert_eq!(fields["gl2gl_fee_msat"], "3");
+        assert_ne!(fields["gl2gl_ms"], "");
+    }
+
+    #[tokio::test]
+    async fn gl2gl_accepts_amount_net_of_opening_fee() {
+        let (sender, receiver, events) = gl2gl_nodes(1_000_000, Some(200), AMOUNT_MSAT - 200);
+        let measurement = run_gl2gl(sender, receiver, &events, false).await;
+
+        assert_eq!(measurement.outcome, Outcome::Ok);
+        assert_eq!(measurement.opening_fee_msat, Some(200));
+    }
+
+    #[tokio::test]
+    async fn gl2gl_fails_if_receiver_got_less() {
+        let (sender, receiver, events) = gl2gl_nodes(1_000_000, None, AMOUNT_MSAT - 1);
+        let measurement = run_gl2gl(sender, receiver, &events, false).await;
+
+        assert_eq!(measurement.outcome, Outcome::Failed);
+        assert_eq!(measurement.kind, Some(FailureKind::ReceiveUnverified));
+        // The payment was still sent, so its measurements are kept
+        assert!(measurement.duration.is_some());
+        assert_eq!(measurement.fee_msat, Some(3));
+        assert_eq!(measurement.opening_fee_msat, Some(0));
+        assert!(measurement.invoice_create.is_some());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gl2gl_unsettled_payment_is_not_retried() {
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+        let (sender, receiver, _) = gl2gl_nodes(1_000_000, None, AMOUNT_MSAT);
+        // The receiver never sees the payment settled
+        let sender = Arc::new(MockSdkClient {
+            invoice_paid_events: None,
+            ..sender
+        });
+        let receiver = Arc::new(receiver);
+        let events = Mutex::new(events_rx);
+        let measurement = with_retry(
+            3,
+            Duration::from_millis(1),
+            Duration::from_millis(1),
+            None,
+            || {
+                pay_gl_2_gl(
+                    sender.clone(),
+                    receiver.clone(),
+                    &events,
+                    AMOUNT_MSAT,
+                    "test-gl2gl",
+                    invoice_options(),
+                    false,
+                    "label".into(),
+                )
+            },
+        )
+        .await;
+        drop(events_tx);
+
+        assert_eq!(sender.sent_labels.lock().unwrap().len(), 1);
+        assert_eq!(measurement.outcome, Outcome::Failed);
+        assert_eq!(measurement.kind, Some(FailureKind::ReceiveUnverified));
+        assert_eq!(
+            measurement.status,
+            "[sdk-rx] Payment not settled within the verification timeout"
+        );
+        assert_eq!(measurement.fee_msat, Some(3));
+        assert!(measurement.invoice_create.is_some());
+        assert_eq!(measurement.settlement, None);
+    }
+
+    #[tokio::test]
+    async fn gl2gl_send_error_records_error_code() {
+        let (_, events_rx) = mpsc::unbounded_channel();
+        let sender = MockSdkClient::default();
+        sender
+            .send_payment
+            .push(Err(SendPaymentError::RouteNotFound {
+                err: "no route".into(),
+            }));
+        let receiver = MockSdkClient::default();
+        // The payment is still attempted if the liquidity can't be checked
+        receiver.node_info.push(Err(SdkError::Generic {
+            err: "offline".into(),
+        }));
+        receiver
+            .receive_payment
+            .push(Ok(received_invoice(PAYMENT_HASH, AMOUNT_MSAT, None)));
+        let measurement = run_gl2gl(sender, receiver, &Mutex::new(events_rx), false).await;
+
+        assert_eq!(measurement.outcome, Outcome::Failed);
+        assert_eq!(measurement.kind, Some(FailureKind::NoRoute));
+        assert_eq!(measurement.error_code, Some("ROUTE_NOT_FOUND"));
+        assert_eq!(measurement.fee_msat, None);
+
+        let fields = csv_fields(gl2gl(), &measurement);
+        assert_eq!(fields["gl2gl_ms"], "");
+        assert_eq!(fields["gl2gl_outcome"], "failed");
+        assert_eq!(fields["gl2gl_failure_kind"], "no_route");
+        assert_eq!(fields["gl2gl_error_code"], "ROUTE_NOT_FOUND");
+        assert_eq!(fields["gl2gl_fee_msat"], "");
+    }
+
+    #[tokio::test]
+    async fn gl2gl_without_inbound_liquidity_can_fail_before_the_invoice() {
+        let (_, events_rx) = mpsc::unbounded_channel();
+        let receiver = MockSdkClient::default();
+        receiver.node_info.push(Ok(node_state(AMOUNT_MSAT - 1)));
+        // Neither node gets any other call
+        let measurement = run_gl2gl(
+            MockSdkClient::default(),
+            receiver,
+            &Mutex::new(events_rx),
+            true,
+        )
+        .await;
+
+        assert_eq!(measurement.outcome, Outcome::Failed);
+        assert_eq!(measurement.kind, Some(FailureKind::InsufficientInbound));
+        assert_eq!(measurement.status, "insufficient_inbound");
+        assert_eq!(
+            csv_fields(gl2gl(), &measurement)["gl2gl_failure_kind"],
+            "insufficient_inbound"
+        );
+    }
+
+    #[tokio::test]
+    async fn gl2gl_without_inbound_liquidity_opens_a_channel() {
+        let (sender, receiver, events) = gl2gl_nodes(0, Some(200), AMOUNT_MSAT - 200);
+        receiver.open_channel_fee.push(Ok(OpenChannelFeeResponse {
+            fee_msat: Some(200),
+            fee_params: OpeningFeeParams {
+                min_msat: 0,
+                proportional: 0,
+                valid_until: String::new(),
+                max_idle_time: 0,
+                max_client_to_self_delay: 0,
+                promise: "promise".into(),
+            },
+        }));
+        let receiver = Arc::new(receiver);
+        let measurement = pay_gl_2_gl(
+            Arc::new(sender),
+            receiver.clone(),
+            &events,
+            AMOUNT_MSAT,
+            "test-gl2gl",
+            invoice_options(),
+            false,
+            "label".into(),
+        )
+        .await;
+
+        assert_eq!(measurement.outcome, Outcome::Ok);
+        assert_eq!(measurement.opening_fee_msat, Some(200));
+        // The invoice is created with the fetched params, even though the config doesn't ask for it
+        let requests = receiver.receive_requests.lock().unwrap();
+        let promises: Vec<_> = requests
+            .iter()
+            .map(|req| {
+                req.opening_fee_params
+                    .as_ref()
+                    .map(|params| &params.promise)
+            })
+            .collect();
+        assert_eq!(promises, [Some(&"promise".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn gl2gl_without_opening_fee_params_lets_the_sdk_pick_them() {
+        let (sender, receiver, events) = gl2gl_nodes(0, Some(200), AMOUNT_MSAT - 200);
+        receiver.open_channel_fee.push(Err(SdkError::Generic {
+            err: "LSP unreachable".into(),
+        }));
+        let receiver = Arc::new(receiver);
+        let measurement = pay_gl_2_gl(
+            Arc::new(sender),
+            receiver.clone(),
+            &events,
+            AMOUNT_MSAT,
+            "test-gl2gl",
+            invoice_options(),
+            false,
+            "label".into(),
+        )
+        .await;
+
+        assert_eq!(measurement.outcome, Outcome::Ok);
+        let requests = receiver.receive_requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].opening_fee_params.is_none());
+    }
+
+    #[tokio::test]
+    async fn gl2gl_times_the_invoice_and_the_payment_separately() {
+        const RECEIVE_LATENCY: Duration = Duration::from_millis(200);
+        const SEND_LATENCY: Duration = Duration::from_millis(20);
+        let (sender, receiver, events) = gl2gl_nodes(1_000_000, None, AMOUNT_MSAT);
+        let sender = MockSdkClient {
+            send_latency: SEND_LATENCY,
+            ..sender
+        };
+        let receiver = MockSdkClient {
+            receive_latency: RECEIVE_LATENCY,
+            ..receiver
+        };
+        let measurement = run_gl2gl(sender, receiver, &events, false).await;
+
+        assert_eq!(measurement.outcome, Outcome::Ok);
+        assert!(measurement.invoice_create.unwrap() >= RECEIVE_LATENCY);
+        let duration = measurement.duration.unwrap();
+        assert!(duration >= SEND_LATENCY);
+        // The payment duration doesn't include creating the invoice
+        assert!(duration < RECEIVE_LATENCY);
+    }
+
+    /// A context of the first iteration with the mocks as nodes, without a deadline
+    fn run_context(
+        sdk_1: Arc<MockSdkClient>,
+        sdk_2: Arc<MockSdkClient>,
+        sdk_2_events: Mutex<UnboundedReceiver<BreezEvent>>,
+        attempts: u32,
+    ) -> RunContext {
+        let (_, sdk_1_events) = mpsc::unbounded_channel();
+        RunContext {
+            sdk_1,
+            sdk_2,
+            timeout_secs: 10,
+            attempts,
+            iteration: 1,
+            deadline: None,
+            payment_permits: Semaphore::new(1),
+            sdk_1_events: Mutex::new(sdk_1_events),
+            sdk_2_events,
+        }
+    }
+
+    #[tokio::test]
+    async fn gl2gl_scenario_pays_sdk_2_with_a_labeled_payment() {
+        let (sender, receiver, events) = gl2gl_nodes(1_000_000, None, AMOUNT_MSAT);
+        let sender = Arc::new(sender);
+        let ctx = run_context(sender.clone(), Arc::new(receiver), events, 1);
+        let res = gl2gl().run(&ctx).await;
+
+        assert_eq!(res.name, "gl2gl");
+        assert_eq!(res.measurement.outcome, Outcome::Ok);
+        assert!(res.measurement.settlement.is_some());
+        let label = res.measurement.payment_label.clone().unwrap();
+        assert!(label.starts_with("pulse-1-gl2gl-"));
+        assert_eq!(*sender.sent_labels.lock().unwrap(), [Some(label)]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn keysend_scenario_retries_with_a_new_label() {
+        let sender = Arc::new(MockSdkClient::default());
+        sender
+            .send_spontaneous_payment
+            .push(Err(SendPaymentError::RouteNotFound {
+                err: "no route".into(),
+            }));
+        sender
+            .send_spontaneous_payment
+            .push(Ok(SendPaymentResponse {
+                payment: payment(PAYMENT_HASH, AMOUNT_MSAT, 5),
+            }));
+        let (_, events_rx) = mpsc::unbounded_channel();
+        let ctx = run_context(
+            sender.clone(),
+            Arc::new(MockSdkClient::default()),
+            Mutex::new(events_rx),
+            2,
+        );
+        let res = gl2keysend().run(&ctx).await;
+
+        assert_eq!(res.measurement.outcome, Outcome::Ok);
+        assert_eq!(res.measurement.status, "Ok (attempts: 2)");
+        assert_eq!(res.measurement.fee_msat, Some(5));
+        let sent_labels = sender.sent_labels.lock().unwrap();
+        assert_eq!(sent_labels.len(), 2);
+        assert_ne!(sent_labels[0], sent_labels[1]);
+        assert_eq!(sent_labels[1], res.measurement.payment_label);
+    }
+
+    #[tokio::test]
+    async fn swap_in_scenario_is_skipped_while_a_swap_is_in_progress() {
+        let receiver = Arc::new(MockSdkClient::default());
+        receiver
+            .receive_onchain
+            .push(Err(ReceiveOnchainError::SwapInProgress {
+                err: "swap in progress".into(),
+            }));
+        let (_, events_rx) = mpsc::unbounded_channel();
+        let ctx = run_context(
+            Arc::new(MockSdkClient::default()),
+            receiver,
+            Mutex::new(events_rx),
+            2,
+        );
+        let res = SwapIn.run(&ctx).await;
+
+        // Skipped, not failed, so it isn't retried either
+        assert_eq!(res.name, "swapin");
+        assert_eq!(res.measurement.outcome, Outcome::Skipped);
+        assert_eq!(
+            res.measurement.status,
+            "Skipped: Swap already in progress: swap in progress"
+        );
+    }
+
+    fn gl2keysend() -> Box<dyn Scenario> {
+        Box::new(Gl2Keysend {
+            node_id: "node".into(),
+            amount_msat: AMOUNT_MSAT,
+        })
+    }
+
+    #[tokio::test]
+    async fn keysend_success_records_fee() {
+        let sender = Arc::new(MockSdkClient::default());
+        sender
+            .send_spontaneous_payment
+            .push(Ok(SendPaymentResponse {
+                payment: payment(PAYMENT_HASH, AMOUNT_MSAT, 5),
+            }));
+        let measurement =
+            pay_gl_2_keysend(sender.clone(), "node", AMOUNT_MSAT, "label".into()).await;
+
+        assert_eq!(measurement.outcome, Outcome::Ok);
+        assert_eq!(measurement.kind, None);
+        assert_eq!(measurement.error_code, None);
+        assert_eq!(measurement.fee_msat, Some(5));
+        assert_eq!(*sender.sent_labels.lock().unwrap(), [Some("label".into())]);
+
+        let fields = csv_fields(gl2keysend(), &measurement);
+        assert_eq!(fields["gl2keysend_outcome"], "ok");
+        assert_eq!(fields["gl2keysend_fee_msat"], "5");
+    }
+
+    #[tokio::test]
+    async fn keysend_timeout_records_error_code() {
+        let sender = Arc::new(MockSdkClient::default());
+        sender
+            .send_spontaneous_payment
+            .push(Err(SendPaymentError::PaymentTimeout {
+                err: "timed out".into(),
+            }));
+        let measurement = pay_gl_2_keysend(sender, "node", AMOUNT_MSAT, "label".into()).await;
+
+        assert_eq!(measurement.outcome, Outcome::Failed);
+        assert_eq!(measurement.kind, Some(FailureKind::Timeout));
+        assert_eq!(measurement.error_code, Some("PAYMENT_TIMEOUT"));
+        assert_eq!(measurement.fee_msat, None);
+
+        let fields = csv_fields(gl2keysend(), &measurement);
+        assert_eq!(fields["gl2keysend_ms"], "");
+        assert_eq!(fields["gl2keysend_failure_kind"], "timeout");
+        assert_eq!(fields["gl2keysend_error_code"], "PAYMENT_TIMEOUT");
+    }
+
+    fn gl2wos() -> Box<dyn Scenario> {
+        Box::new(Gl2LnAddress {
+            name: "gl2wos".into(),
+            address: "user@example.com".into(),
+            amount_msat: AMOUNT_MSAT,
+            comment: "test-gl2lnurl".into(),
+            clamp_amount: false,
+        })
+    }
+
+    fn lnurl_pay_data(min_sendable: u64, max_sendable: u64) -> LnUrlPayRequestData {
+        LnUrlPayRequestData {
+            callback: "https://example.com/callback".into(),
+            min_sendable,
+            max_sendable,
+            metadata_str: "[]".into(),
+            comment_allowed: 0,
+            domain: "example.com".into(),
+            allows_nostr: false,
+            nostr_pubkey: None,
+            ln_address: Some("user@example.com".into()),
+        }
+    }
+
+    async fn run_lnurl_pay(
+        sender: &MockSdkClient,
+        data: LnUrlPayRequestData,
+        clamp_amount: bool,
+    ) -> Measurement {
+        pay_lnurl_pay(
+            sender,
+            data,
+            "user@example.com",
+            AMOUNT_MSAT,
+            "test-gl2lnurl",
+            clamp_amount,
+            "label".into(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn ln_address_success_records_fee() {
+        let sender = MockSdkClient::default();
+        sender.lnurl_pay.push(Ok(LnUrlPayResult::EndpointSuccess {
+            data: LnUrlPaySuccessData {
+                payment: payment(PAYMENT_HASH, AMOUNT_MSAT, 2),
+                success_action: None,
+            },
+        }));
+        let measurement = run_lnurl_pay(&sender, lnurl_pay_data(1, 1_000_000), false).await;
+
+        assert_eq!(measurement.outcome, Outcome::Ok);
+        assert_eq!(measurement.kind, None);
+        assert_eq!(measurement.error_code, None);
+        assert_eq!(measurement.fee_msat, Some(2));
+
+        let fields = csv_fields(gl2wos(), &measurement);
+        assert_eq!(fields["gl2wos_outcome"], "ok");
+        assert_eq!(fields["gl2wos_fee_msat"], "2");
+    }
+
+    #[tokio::test]
+    async fn ln_address_endpoint_error_is_not_an_sdk_error() {
+        let sender = MockSdkClient::default();
+        sender.lnurl_pay.push(Ok(LnUrlPayResult::EndpointError {
+            data: LnUrlErrorData {
+                reason: "Service unavailable".into(),
+            },
+        }));
+        let measurement = run_lnurl_pay(&sender, lnurl_pay_data(1, 1_000_000), false).await;
+
+        assert_eq!(measurement.outcome, Outcome::Failed);
+        assert_eq!(measurement.kind, Some(FailureKind::LnurlEndpoint));
+        assert_eq!(measurement.error_code, None);
+        assert_eq!(measurement.status, "Service unavailable");
+
+        let fields = csv_fields(gl2wos(), &measurement);
+        assert_eq!(fields["gl2wos_ms"], "");
+        assert_eq!(fields["gl2wos_failure_kind"], "lnurl_endpoint");
+        assert_eq!(fields["gl2wos_error_code"], "");
+    }
+
+    #[tokio::test]
+    async fn ln_address_sdk_error_records_error_code() {
+        let sender = MockSdkClient::default();
+        sender.lnurl_pay.push(Err(LnUrlPayError::RouteTooExpensive {
+            err: "fee too high".into(),
+        }));
+        let measurement = run_lnurl_pay(&sender, lnurl_pay_data(1, 1_000_000), false).await;
+
+        assert_eq!(measurement.outcome, Outcome::Failed);
+        assert_eq!(measurement.kind, Some(FailureKind::NoRoute));
+        assert_eq!(measurement.error_code, Some("ROUTE_TOO_EXPENSIVE"));
+        assert_eq!(measurement.fee_msat, None);
+    }
+
+    #[tokio::test]
+    async fn ln_address_out_of_range_amount_fails_without_paying() {
+        // The mock panics if it gets paid
+        let sender = MockSdkClient::default();
+        let measurement = run_lnurl_pay(&sender, lnurl_pay_data(2000, 5000), false).await;
+
+        assert_eq!(measurement.outcome, Outcome::Failed);
+        assert_eq!(measurement.kind, Some(FailureKind::AmountOutOfRange));
+        assert_eq!(measurement.error_code, None);
+    }
+
+    #[tokio::test]
+    async fn ln_address_out_of_range_amount_can_be_clamped() {
+        let sender = MockSdkClient::default();
+        sender.lnurl_pay.push(Ok(LnUrlPayResult::EndpointSuccess {
+            data: LnUrlPaySuccessData {
+                payment: payment(PAYMENT_HASH, 2000, 1),
+                success_action: None,
+            },
+        }));
+        let measurement = run_lnurl_pay(&sender, lnurl_pay_data(2000, 5000), true).await;
+
+        assert_eq!(measurement.outcome, Outcome::Ok);
+        assert_eq!(measurement.fee_msat, Some(1));
+    }
+}
@@ -0,0 +1,405 @@
+use async_trait::async_trait;
+use breez_sdk_core::*;
+
+/// The SDK calls made by the scenarios, so they can run against something other than a connected
+/// [BreezServices]
+#[async_trait]
+pub(crate) trait SdkClient: Send + Sync {
+    async fn receive_payment(
+        &self,
+        req: ReceivePaymentRequest,
+    ) -> Result<ReceivePaymentResponse, ReceivePaymentError>;
+
+    async fn send_payment(
+        &self,
+        req: SendPaymentRequest,
+    ) -> Result<SendPaymentResponse, SendPaymentError>;
+
+    async fn send_spontaneous_payment(
+        &self,
+        req: SendSpontaneousPaymentRequest,
+    ) -> Result<SendPaymentResponse, SendPaymentError>;
+
+    async fn lnurl_pay(&self, req: LnUrlPayRequest) -> Result<LnUrlPayResult, LnUrlPayError>;
+
+    async fn open_channel_fee(
+        &self,
+        req: OpenChannelFeeRequest,
+    ) -> SdkResult<OpenChannelFeeResponse>;
+
+    async fn payment_by_hash(&self, hash: String) -> SdkResult<Option<Payment>>;
+
+    async fn receive_onchain(
+        &self,
+        req: ReceiveOnchainRequest,
+    ) -> Result<SwapInfo, ReceiveOnchainError>;
+
+    async fn in_progress_swap(&self) -> SdkResult<Option<SwapInfo>>;
+
+    async fn recommended_fees(&self) -> SdkResult<RecommendedFees>;
+
+    async fn prepare_onchain_payment(
+        &self,
+        req: PrepareOnchainPaymentRequest,
+    ) -> Result<PrepareOnchainPaymentResponse, SendOnchainError>;
+
+    async fn pay_onchain(
+        &self,
+        req: PayOnchainRequest,
+    ) -> Result<PayOnchainResponse, SendOnchainError>;
+
+    async fn lnurl_withdraw(
+        &self,
+        req: LnUrlWithdrawRequest,
+    ) -> Result<LnUrlWithdrawResult, LnUrlWithdrawError>;
+
+    async fn lnurl_auth(
+        &self,
+        req_data: LnUrlAuthRequestData,
+    ) -> Result<LnUrlCallbackStatus, LnUrlAuthError>;
+
+    fn node_info(&self) -> SdkResult<NodeState>;
+
+    async fn disconnect(&self) -> SdkResult<()>;
+}
+
+#[async_trait]
+impl SdkClient for BreezServices {
+    async fn receive_payment(
+        &self,
+        req: ReceivePaymentRequest,
+    ) -> Result<ReceivePaymentResponse, ReceivePaymentError> {
+        BreezServices::receive_payment(self, req).await
+    }
+
+    async fn send_payment(
+        &self,
+        req: SendPaymentRequest,
+    ) -> Result<SendPaymentResponse, SendPaymentError> {
+        BreezServices::send_payment(self, req).await
+    }
+
+    async fn send_spontaneous_payment(
+        &self,
+        req: SendSpontaneousPaymentRequest,
+    ) -> Result<SendPaymentResponse, SendPaymentError> {
+        BreezServices::send_spontaneous_payment(self, req).await
+    }
+
+    async fn lnurl_pay(&self, req: LnUrlPayRequest) -> Result<LnUrlPayResult, LnUrlPayError> {
+        BreezServices::lnurl_pay(self, req).await
+    }
+
+    async fn open_channel_fee(
+        &self,
+        req: OpenChannelFeeRequest,
+    ) -> SdkResult<OpenChannelFeeResponse> {
+        BreezServices::open_channel_fee(self, req).await
+    }
+
+    async fn payment_by_hash(&self, hash: String) -> SdkResult<Option<Payment>> {
+        BreezServices::payment_by_hash(self, hash).await
+    }
+
+    async fn receive_onchain(
+        &self,
+        req: ReceiveOnchainRequest,
+    ) -> Result<SwapInfo, ReceiveOnchainError> {
+        BreezServices::receive_onchain(self, req).await
+    }
+
+    async fn in_progress_swap(&self) -> SdkResult<Option<SwapInfo>> {
+        BreezServices::in_progress_swap(self).await
+    }
+
+    async fn recommended_fees(&self) -> SdkResult<RecommendedFees> {
+        BreezServices::recommended_fees(self).await
+    }
+
+    async fn prepare_onchain_payment(
+        &self,
+        req: PrepareOnchainPaymentRequest,
+    ) -> Result<PrepareOnchainPaymentResponse, SendOnchainError> {
+        BreezServices::prepare_onchain_payment(self, req).await
+    }
+
+    async fn pay_onchain(
+        &self,
+        req: PayOnchainRequest,
+    ) -> Result<PayOnchainResponse, SendOnchainError> {
+        BreezServices::pay_onchain(self, req).await
+    }
+
+    async fn lnurl_withdraw(
+        &self,
+        req: LnUrlWithdrawRequest,
+    ) -> Result<LnUrlWithdrawResult, LnUrlWithdrawError> {
+        BreezServices::lnurl_withdraw(self, req).await
+    }
+
+    async fn lnurl_auth(
+        &self,
+        req_data: LnUrlAuthRequestData,
+    ) -> Result<LnUrlCallbackStatus, LnUrlAuthError> {
+        BreezServices::lnurl_auth(self, req_data).await
+    }
+
+    fn node_info(&self) -> SdkResult<NodeState> {
+        BreezServices::node_info(self)
+    }
+
+    async fn disconnect(&self) -> SdkResult<()> {
+        BreezServices::disconnect(self).await
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod mock {
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use breez_sdk_core::*;
+    use tokio::sync::mpsc::UnboundedSender;
+
+    use super::SdkClient;
+
+    /// Responses to one kind of call, returned in the order they were queued
+    pub(crate) struct Responses<T>(Mutex<VecDeque<T>>);
+
+    impl<T> Default for Responses<T> {
+        fn default() -> Self {
+            Self(Mutex::new(VecDeque::new()))
+        }
+    }
+
+    impl<T> Responses<T> {
+        pub(crate) fn push(&self, res: T) {
+            self.0.lock().unwrap().push_back(res);
+        }
+
+        fn next(&self, call: &str) -> T {
+            self.0
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or_else(|| panic!("Unexpected call to {call}, no response queued"))
+        }
+    }
+
+    /// An [SdkClient] answering every call with the next response queued for it. A call without a
+    /// queued response panics, so tests notice calls they didn't expect.
+    #[derive(Default)]
+    pub(crate) struct MockSdkClient {
+        pub(crate) receive_payment: Responses<Result<ReceivePaymentResponse, ReceivePaymentError>>,
+        pub(crate) send_payment: Responses<Result<SendPaymentResponse, SendPaymentError>>,
+        pub(crate) send_spontaneous_payment:
+            Responses<Result<SendPaymentResponse, SendPaymentError>>,
+        pub(crate) lnurl_pay: Responses<Result<LnUrlPayResult, LnUrlPayError>>,
+        pub(crate) open_channel_fee: Responses<SdkResult<OpenChannelFeeResponse>>,
+        pub(crate) payment_by_hash: Responses<SdkResult<Option<Payment>>>,
+        pub(crate) receive_onchain: Responses<Result<SwapInfo, ReceiveOnchainError>>,
+        pub(crate) in_progress_swap: Responses<SdkResult<Option<SwapInfo>>>,
+        pub(crate) recommended_fees: Responses<SdkResult<RecommendedFees>>,
+        pub(crate) prepare_onchain_payment:
+            Responses<Result<PrepareOnchainPaymentResponse, SendOnchainError>>,
+        pub(crate) pay_onchain: Responses<Result<PayOnchainResponse, SendOnchainError>>,
+        pub(crate) lnurl_withdraw: Responses<Result<LnUrlWithdrawResult, LnUrlWithdrawError>>,
+        pub(crate) lnurl_auth: Responses<Result<LnUrlCallbackStatus, LnUrlAuthError>>,
+        pub(crate) node_info: Responses<SdkResult<NodeState>>,
+        /// How long every [SdkClient::receive_payment] call takes
+        pub(crate) receive_latency: Duration,
+        /// How long every call sending a payment takes
+        pub(crate) send_latency: Duration,
+        /// If set, every successful [SdkClient::send_payment] also sends an `InvoicePaid` event
+        /// for the hash of the returned payment here, like the receiving node would
+        pub(crate) invoice_paid_events: Option<UnboundedSender<BreezEvent>>,
+        /// Requests of the invoices created so far
+        pub(crate) receive_requests: Mutex<Vec<ReceivePaymentRequest>>,
+        /// Labels of the payments sent so far
+        pub(crate) sent_labels: Mutex<Vec<Option<String>>>,
+        /// How often [SdkClient::disconnect] was called
+        pub(crate) disconnects: AtomicUsize,
+    }
+
+    impl MockSdkClient {
+        pub(crate) fn disconnects(&self) -> usize {
+            self.disconnects.load(Ordering::SeqCst)
+        }
+    }
+
+    #[async_trait]
+    impl SdkClient for MockSdkClient {
+        async fn receive_payment(
+            &self,
+            req: ReceivePaymentRequest,
+        ) -> Result<ReceivePaymentResponse, ReceivePaymentError> {
+            self.receive_requests.lock().unwrap().push(req);
+            tokio::time::sleep(self.receive_latency).await;
+            self.receive_payment.next("receive_payment")
+        }
+
+        async fn send_payment(
+            &self,
+            req: SendPaymentRequest,
+        ) -> Result<SendPaymentResponse, SendPaymentError> {
+            self.sent_labels.lock().unwrap().push(req.label);
+            tokio::time::sleep(self.send_latency).await;
+            let res = self.send_payment.next("send_payment");
+            if let (Ok(res), Some(events)) = (&res, &self.invoice_paid_events) {
+                if let PaymentDetails::Ln { data } = &res.payment.details {
+                    let _ = events.send(BreezEvent::InvoicePaid {
+                        details: InvoicePaidDetails {
+                            payment_hash: data.payment_hash.clone(),
+                            bolt11: req.bolt11,
+                            payment: None,
+                        },
+                    });
+                }
+            }
+            res
+        }
+
+        async fn send_spontaneous_payment(
+            &self,
+            req: SendSpontaneousPaymentRequest,
+        ) -> Result<SendPaymentResponse, SendPaymentError> {
+            self.sent_labels.lock().unwrap().push(req.label);
+            tokio::time::sleep(self.send_latency).await;
+            self.send_spontaneous_payment
+                .next("send_spontaneous_payment")
+        }
+
+        async fn lnurl_pay(&self, req: LnUrlPayRequest) -> Result<LnUrlPayResult, LnUrlPayError> {
+            self.sent_labels.lock().unwrap().push(req.payment_label);
+            tokio::time::sleep(self.send_latency).await;
+            self.lnurl_pay.next("lnurl_pay")
+        }
+
+        async fn open_channel_fee(
+            &self,
+            _req: OpenChannelFeeRequest,
+        ) -> SdkResult<OpenChannelFeeResponse> {
+            self.open_channel_fee.next("open_channel_fee")
+        }
+
+        async fn payment_by_hash(&self, _hash: String) -> SdkResult<Option<Payment>> {
+            self.payment_by_hash.next("payment_by_hash")
+        }
+
+        async fn receive_onchain(
+            &self,
+            _req: ReceiveOnchainRequest,
+        ) -> Result<SwapInfo, ReceiveOnchainError> {
+            self.receive_onchain.next("receive_onchain")
+        }
+
+        async fn in_progress_swap(&self) -> SdkResult<Option<SwapInfo>> {
+            self.in_progress_swap.next("in_progress_swap")
+        }
+
+        async fn recommended_fees(&self) -> SdkResult<RecommendedFees> {
+            self.recommended_fees.next("recommended_fees")
+        }
+
+        async fn prepare_onchain_payment(
+            &self,
+            _req: PrepareOnchainPaymentRequest,
+        ) -> Result<PrepareOnchainPaymentResponse, SendOnchainError> {
+            self.prepare_onchain_payment.next("prepare_onchain_payment")
+        }
+
+        async fn pay_onchain(
+            &self,
+            _req: PayOnchainRequest,
+        ) -> Result<PayOnchainResponse, SendOnchainError> {
+            self.pay_onchain.next("pay_onchain")
+        }
+
+        async fn lnurl_withdraw(
+            &self,
+            _req: LnUrlWithdrawRequest,
+        ) -> Result<LnUrlWithdrawResult, LnUrlWithdrawError> {
+            self.lnurl_withdraw.next("lnurl_withdraw")
+        }
+
+        async fn lnurl_auth(
+            &self,
+            _req_data: LnUrlAuthRequestData,
+        ) -> Result<LnUrlCallbackStatus, LnUrlAuthError> {
+            self.lnurl_auth.next("lnurl_auth")
+        }
+
+        fn node_info(&self) -> SdkResult<NodeState> {
+            self.node_info.next("node_info")
+        }
+
+        async fn disconnect(&self) -> SdkResult<()> {
+            self.disconnects.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    /// A complete Lightning payment with the given hash, amount and fee
+    pub(crate) fn payment(payment_hash: &str, amount_msat: u64, fee_msat: u64) -> Payment {
+        Payment {
+            id: payment_hash.into(),
+            payment_type: PaymentType::Sent,
+            payment_time: 0,
+            amount_msat,
+            fee_msat,
+            status: PaymentStatus::Complete,
+            error: None,
+            description: None,
+            details: PaymentDetails::Ln {
+                data: LnPaymentDetails {
+                    payment_hash: payment_hash.into(),
+                    label: String::new(),
+                    destination_pubkey: String::new(),
+                    payment_preimage: String::new(),
+                    keysend: false,
+                    bolt11: String::new(),
+                    open_channel_bolt11: None,
+                    lnurl_success_action: None,
+                    lnurl_pay_domain: None,
+                    lnurl_pay_comment: None,
+                    ln_address: None,
+                    lnurl_metadata: None,
+                    lnurl_withdraw_endpoint: None,
+                    swap_info: None,
+                    reverse_swap_info: None,
+                    pending_expiration_block: None,
+                },
+            },
+            metadata: None,
+        }
+    }
+
+    /// A created invoice for the amount, as the receiver would return it
+    pub(crate) fn received_invoice(
+        payment_hash: &str,
+        amount_msat: u64,
+        opening_fee_msat: Option<u64>,
+    ) -> ReceivePaymentResponse {
+        ReceivePaymentResponse {
+            ln_invoice: LNInvoice {
+                bolt11: format!("lnbc-{payment_hash}"),
+                network: Network::Bitcoin,
+                payee_pubkey: String::new(),
+                payment_hash: payment_hash.into(),
+                description: None,
+                description_hash: None,
+                amount_msat: Some(amount_msat),
+                timestamp: 0,
+                expiry: 60,
+                routing_hints: vec![],
+                payment_secret: vec![],
+                min_final_cltv_expiry_delta: 144,
+            },
+            opening_fee_params: None,
+            opening_fee_msat,
+        }
+    }
+}
@@ -0,0 +1,576 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::Arc;
+use std::thread;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::format::{Item, StrftimeItems};
+use chrono::DateTime;
+use log::error;
+use tokio::runtime;
+use tokio::sync::mpsc;
+
+use crate::scenarios::Scenario;
+use crate::IterationResult;
+
+/// An output to which the result of every iteration is written
+///
+/// Sinks that send the results over the network only log delivery errors instead of returning
+/// them, so an unreachable service doesn't stop the run.
+#[async_trait]
+pub(crate) trait Sink: Send {
+    /// Short name of the sink, for the logs
+    fn name(&self) -> &'static str;
+
+    async fn record(&mut self, iteration_res: &IterationResult) -> Result<()>;
+
+    /// Write out anything still buffered, called once before exiting
+    async fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// How many iteration results can wait for the [SinkWriter] before recording a new one waits too.
+///
+/// This is also how many results are lost at most if the process crashes, as opposed to being
+/// stopped with a shutdown signal, in which case everything is written.
+const WRITE_QUEUE_LEN: usize = 16;
+
+/// Writes the iteration results to the sinks on a separate thread, so slow disk or network writes
+/// don't delay the next iteration.
+///
+/// The thread has its own runtime, so the sinks writing files or databases synchronously don't
+/// block the workers running the scenarios. A sink that fails is logged and the others are still
+/// written to, as are the later results.
+pub(crate) struct SinkWriter {
+    results: Option<mpsc::Sender<Arc<IterationResult>>>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl SinkWriter {
+    pub(crate) fn spawn(mut sinks: Vec<Box<dyn Sink>>) -> Result<Self> {
+        let (results_tx, mut results_rx) = mpsc::channel::<Arc<IterationResult>>(WRITE_QUEUE_LEN);
+        let runtime = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let handle = thread::Builder::new()
+            .name("sink-writer".into())
+            .spawn(move || {
+                runtime.block_on(async move {
+                    while let Some(iteration_res) = results_rx.recv().await {
+                        for sink in &mut sinks {
+                            if let Err(e) = sink.record(&iteration_res).await {
+                                error!("Failed to write the iteration to {}: {e}", sink.name());
+                            }
+                        }
+                    }
+                    for sink in &mut sinks {
+                        if let Err(e) = sink.flush().await {
+                            error!("Failed to flush {}: {e}", sink.name());
+                        }
+                    }
+                })
+            })?;
+        Ok(Self {
+            results: Some(results_tx),
+            handle: Some(handle),
+        })
+    }
+
+    /// Queue the iteration result to be written to every sink.
+    ///
+    /// Only fails if the writer thread is gone, which means it panicked.
+    pub(crate) async fn record(&mut self, iteration_res: Arc<IterationResult>) -> Result<()> {
+        let Some(results) = &self.results else {
+            return Err(anyhow!("Sink writer already finished"));
+        };
+        if results.send(iteration_res).await.is_err() {
+            self.results.take();
+            self.join().await?;
+            return Err(anyhow!("Sink writer stopped"));
+        }
+        Ok(())
+    }
+
+    /// Write all queued results, flush the sinks and stop the writer thread
+    pub(crate) async fn finish(mut self) -> Result<()> {
+        self.results.take();
+        self.join().await
+    }
+
+    async fn join(&mut self) -> Result<()> {
+        let Some(handle) = self.handle.take() else {
+            return Ok(());
+        };
+        tokio::task::spawn_blocking(move || handle.join())
+            .await?
+            .map_err(|_| anyhow!("Sink writer panicked"))
+    }
+}
+
+impl Drop for SinkWriter {
+    fn drop(&mut self) {
+        if self.handle.is_some() {
+            error!("Sink writer dropped without finishing, queued results may be lost");
+        }
+    }
+}
+
+/// Appends one row per iteration to a CSV file
+///
+/// The path can contain `strftime` placeholders like `%Y-%m-%d`, formatted with the (UTC) date of
+/// each iteration, so a new file is started every day.
+///
+/// If `columns` are given, only those are written, in that order, instead of all of them.
+pub(crate) struct CsvSink {
+    path: String,
+    header: Vec<String>,
+    /// Positions of the written columns in the full header, `None` to write all of them
+    projection: Option<Vec<usize>>,
+}
+
+impl CsvSink {
+    pub(crate) fn new(
+        path: &str,
+        scenarios: &[Box<dyn Scenario>],
+        fiat_currency: &str,
+        columns: Option<&[String]>,
+    ) -> Result<Self> {
+        if StrftimeItems::new(path).any(|item| item == Item::Error) {
+            return Err(anyhow!("Invalid date placeholder in CSV path {path}"));
+        }
+        let full_header = csv_header(scenarios, fiat_currency);
+        let Some(columns) = columns else {
+            return Ok(Self {
+                path: path.into(),
+                header: full_header,
+                projection: None,
+            });
+        };
+        Ok(Self {
+            path: path.into(),
+            header: columns.to_vec(),
+            projection: Some(csv_projection(columns, &full_header)?),
+        })
+    }
+
+    /// Path of the file for the iteration that started at `start_ts`
+    fn path_at(&self, start_ts: u64) -> String {
+        match DateTime::from_timestamp(start_ts as i64, 0) {
+            Some(date) => date.format(&self.path).to_string(),
+            None => self.path.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl Sink for CsvSink {
+    fn name(&self) -> &'static str {
+        "CSV"
+    }
+
+    async fn record(&mut self, iteration_res: &IterationResult) -> Result<()> {
+        let path = self.path_at(iteration_res.start_ts);
+        let mut record = csv_record(iteration_res);
+        if let Some(projection) = &self.projection {
+            record = projection.iter().map(|&i| record[i].clone()).collect();
+        }
+        append_csv_record(&path, &self.header, &record)
+    }
+}
+
+/// Appends one JSON object per iteration to a JSON Lines file
+pub(crate) struct JsonlSink {
+    path: String,
+}
+
+impl JsonlSink {
+    pub(crate) fn new(path: &str) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl Sink for JsonlSink {
+    fn name(&self) -> &'static str {
+        "JSON Lines"
+    }
+
+    async fn record(&mut self, iteration_res: &IterationResult) -> Result<()> {
+        append_jsonl_line(&self.path, iteration_res)
+    }
+}
+
+/// Column names of the iterations CSV, with one column group per scenario
+pub(crate) fn csv_header(scenarios: &[Box<dyn Scenario>], fiat_currency: &str) -> Vec<String> {
+    let mut header: Vec<String> = vec![
+        "start_ts".into(),
+        "iso_ts".into(),
+        "host".into(),
+        "version".into(),
+        "git_sha".into(),
+        "sdk1_connect_ms".into(),
+        "sdk2_connect_ms".into(),
+        "cold_start_payment_ms".into(),
+        "amount_fiat".into(),
+        "fiat_amount_msat".into(),
+        "reconnected".into(),
+        format!("btc_{}_rate", fiat_currency.to_lowercase()),
+        "sender_balance_before".into(),
+        "sender_balance_after".into(),
+        "receiver_balance_before".into(),
+        "receiver_balance_after".into(),
+    ];
+    for scenario in scenarios {
+        let name = scenario.name();
+        header.extend([
+            format!("{name}_ms"),
+            format!("{name}_outcome"),
+            format!("{name}_status"),
+            format!("{name}_failure_kind"),
+            format!("{name}_error_code"),
+            format!("{name}_fee_msat"),
+            format!("{name}_invoice_ms"),
+            format!("{name}_settlement_ms"),
+            format!("{name}_opening_fee_msat"),
+            format!("{name}_payment_label"),
+        ]);
+    }
+    header
+}
+
+/// Check that every one of the `columns` is in the CSV header of these scenarios
+pub(crate) fn check_csv_columns(
+    columns: &[String],
+    scenarios: &[Box<dyn Scenario>],
+    fiat_currency: &str,
+) -> Result<()> {
+    csv_projection(columns, &csv_header(scenarios, fiat_currency)).map(|_| ())
+}
+
+/// Positions of the `columns` in the full header, failing if one isn't known
+fn csv_projection(columns: &[String], full_header: &[String]) -> Result<Vec<usize>> {
+    columns
+        .iter()
+        .map(|column| {
+            full_header
+                .iter()
+                .position(|known| known == column)
+                .ok_or_else(|| anyhow!("Unknown CSV column {column}"))
+        })
+        .collect()
+}
+
+/// Fields of the iterations CSV, matching [csv_header].
+///
+/// This takes the whole iteration rather than only its start time and scenario results, since the
+/// row also has the per-iteration columns like the connect times, the fiat amount and the balances.
+/// A missing value, like the duration of a failed scenario, is an empty field.
+pub(crate) fn csv_record(iteration_res: &IterationResult) -> Vec<String> {
+    let mut record = vec![
+        iteration_res.start_ts.to_string(),
+        iteration_res.iso_ts.clone(),
+        iteration_res.metadata.host.clone(),
+        iteration_res.metadata.version.into(),
+        iteration_res.metadata.git_sha.into(),
+        iteration_res.sdk1_connect_ms.to_string(),
+        iteration_res.sdk2_connect_ms.to_string(),
+        csv_opt(iteration_res.cold_start_payment_ms),
+        iteration_res
+            .amount_fiat
+            .map(|amount| amount.to_string())
+            .unwrap_or_default(),
+        csv_opt(iteration_res.fiat_amount_msat),
+        iteration_res.reconnected.to_string(),
+        iteration_res
+            .btc_rate
+            .map(|rate| rate.to_string())
+            .unwrap_or_default(),
+        csv_opt(iteration_res.sender_balance_before_msat),
+        csv_opt(iteration_res.sender_balance_after_msat),
+        csv_opt(iteration_res.receiver_balance_before_msat),
+        csv_opt(iteration_res.receiver_balance_after_msat),
+    ];
+    for scenario in &iteration_res.scenarios {
+        let res = &scenario.measurement;
+        record.extend([
+            csv_opt(res.duration_ms()),
+            res.outcome.as_str().into(),
+            res.status.clone(),
+            res.kind.map(|kind| kind.to_string()).unwrap_or_default(),
+            res.error_code.unwrap_or_default().into(),
+            csv_opt(res.fee_msat),
+            csv_opt(res.invoice_create_ms()),
+            csv_opt(res.settlement_ms()),
+            csv_opt(res.opening_fee_msat),
+            res.payment_label.clone().unwrap_or_default(),
+        ]);
+    }
+    record
+}
+
+/// Append a record to the iterations CSV, preceded by the header if the file is new
+fn append_csv_record(csv_path: &str, header: &[String], record: &[String]) -> Result<()> {
+    // Only write the header if the file is new or still empty
+    let write_header = std::fs::metadata(csv_path)
+        .map(|m| m.len() == 0)
+        .unwrap_or(true);
+
+    let file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(csv_path)?;
+    let mut wtr = csv::Writer::from_writer(file);
+    if write_header {
+        wtr.write_record(header)?;
+    }
+    wtr.write_record(record)?;
+    wtr.flush()?;
+
+    Ok(())
+}
+
+/// Format an optional CSV value, leaving the field empty if not set
+fn csv_opt(value: Option<u64>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// Append the iteration result as one line to the JSONL file
+fn append_jsonl_line(jsonl_path: &str, iteration_res: &IterationResult) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(jsonl_path)?;
+    serde_json::to_writer(&mut file, iteration_res)?;
+    writeln!(file)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use super::*;
+    use crate::scenarios::{FailureKind, Gl2Keysend, Gl2LnAddress, Measurement, ScenarioResult};
+
+    fn scenarios() -> Vec<Box<dyn Scenario>> {
+        vec![
+            Box::new(Gl2LnAddress {
+                name: "gl2wos".into(),
+                address: "user@example.com".into(),
+                amount_msat: 1000,
+                comment: "test-gl2lnurl".into(),
+                clamp_amount: false,
+            }),
+            Box::new(Gl2Keysend {
+                node_id: "node".into(),
+                amount_msat: 1000,
+            }),
+        ]
+    }
+
+    fn ok(duration_ms: u64, fee_msat: u64) -> Measurement {
+        Measurement {
+            duration: Some(Duration::from_millis(duration_ms)),
+            fee_msat: Some(fee_msat),
+            ..Measurement::ok(std::time::Instant::now())
+        }
+    }
+
+    fn failed() -> Measurement {
+        Measurement {
+            error_code: Some("ROUTE_NOT_FOUND"),
+            ..Measurement::err(FailureKind::NoRoute, "no route")
+        }
+    }
+
+    /// The CSV fields of an iteration with these gl2wos and gl2keysend results, by column name
+    fn csv_fields(gl2wos: Measurement, gl2keysend: Measurement) -> Vec<(String, String)> {
+        let header = csv_header(&scenarios(), "USD");
+        let record = csv_record(&IterationResult::with_scenarios(vec![
+            ScenarioResult::new("gl2wos", gl2wos),
+            ScenarioResult::new("gl2keysend", gl2keysend),
+        ]));
+        assert_eq!(header.len(), record.len());
+        header.into_iter().zip(record).collect()
+    }
+
+    fn field<'a>(fields: &'a [(String, String)], column: &str) -> &'a str {
+        fields
+            .iter()
+            .find(|(name, _)| name == column)
+            .map(|(_, value)| value.as_str())
+            .unwrap_or_else(|| panic!("No column {column}"))
+    }
+
+    #[test]
+    fn header_has_a_column_group_per_scenario() {
+        let header = csv_header(&scenarios(), "EUR");
+        assert_eq!(header[0], "start_ts");
+        assert!(header.contains(&"btc_eur_rate".to_string()));
+        assert!(header.contains(&"gl2wos_ms".to_string()));
+        assert!(header.contains(&"gl2keysend_payment_label".to_string()));
+        let gl2wos_ms = header.iter().position(|c| c == "gl2wos_ms").unwrap();
+        let gl2keysend_ms = header.iter().position(|c| c == "gl2keysend_ms").unwrap();
+        assert_eq!(gl2keysend_ms - gl2wos_ms, 10);
+    }
+
+    #[test]
+    fn all_success_row() {
+        let fields = csv_fields(ok(1234, 2), ok(567, 3));
+        assert_eq!(field(&fields, "start_ts"), "1700000000");
+        assert_eq!(field(&fields, "iso_ts"), "2023-11-14T22:13:20Z");
+        assert_eq!(field(&fields, "host"), "test-host");
+        assert_eq!(field(&fields, "sdk1_connect_ms"), "1200");
+        assert_eq!(field(&fields, "gl2wos_ms"), "1234");
+        assert_eq!(field(&fields, "gl2wos_outcome"), "ok");
+        assert_eq!(field(&fields, "gl2wos_status"), "Ok");
+        assert_eq!(field(&fields, "gl2wos_failure_kind"), "");
+        assert_eq!(field(&fields, "gl2wos_error_code"), "");
+        assert_eq!(field(&fields, "gl2wos_fee_msat"), "2");
+        assert_eq!(field(&fields, "gl2keysend_ms"), "567");
+        assert_eq!(field(&fields, "gl2keysend_fee_msat"), "3");
+    }
+
+    #[test]
+    fn all_failure_row() {
+        let fields = csv_fields(failed(), failed());
+        for scenario in ["gl2wos", "gl2keysend"] {
+            assert_eq!(field(&fields, &format!("{scenario}_ms")), "");
+            assert_eq!(field(&fields, &format!("{scenario}_outcome")), "failed");
+            assert_eq!(field(&fields, &format!("{scenario}_status")), "no route");
+            assert_eq!(
+                field(&fields, &format!("{scenario}_failure_kind")),
+                "no_route"
+            );
+            assert_eq!(
+                field(&fields, &format!("{scenario}_error_code")),
+                "ROUTE_NOT_FOUND"
+            );
+            assert_eq!(field(&fields, &format!("{scenario}_fee_msat")), "");
+        }
+    }
+
+    #[test]
+    fn mixed_row() {
+        let fields = csv_fields(ok(1234, 2), failed());
+        assert_eq!(field(&fields, "gl2wos_ms"), "1234");
+        assert_eq!(field(&fields, "gl2wos_outcome"), "ok");
+        assert_eq!(field(&fields, "gl2keysend_ms"), "");
+        assert_eq!(field(&fields, "gl2keysend_outcome"), "failed");
+        assert_eq!(field(&fields, "gl2keysend_failure_kind"), "no_route");
+    }
+
+    #[test]
+    fn skipped_row() {
+        let fields = csv_fields(ok(1234, 2), Measurement::skipped("not enabled"));
+        assert_eq!(field(&fields, "gl2keysend_ms"), "");
+        assert_eq!(field(&fields, "gl2keysend_outcome"), "skipped");
+        assert_eq!(field(&fields, "gl2keysend_status"), "Skipped: not enabled");
+        assert_eq!(field(&fields, "gl2keysend_failure_kind"), "");
+        assert_eq!(field(&fields, "gl2keysend_error_code"), "");
+        assert_eq!(field(&fields, "gl2keysend_fee_msat"), "");
+    }
+
+    fn temp_csv_path() -> String {
+        std::env::temp_dir()
+            .join(format!("sdk_pulse-test-{}.csv", uuid::Uuid::new_v4()))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[tokio::test]
+    async fn sink_writes_only_the_selected_columns_in_order() {
+        let path = temp_csv_path();
+        let columns = ["gl2keysend_ms".to_string(), "start_ts".to_string()];
+        let mut sink = CsvSink::new(&path, &scenarios(), "USD", Some(&columns)).unwrap();
+        let iteration_res = IterationResult::with_scenarios(vec![
+            ScenarioResult::new("gl2wos", failed()),
+            ScenarioResult::new("gl2keysend", ok(567, 3)),
+        ]);
+        sink.record(&iteration_res).await.unwrap();
+        sink.record(&iteration_res).await.unwrap();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            written,
+            "gl2keysend_ms,start_ts\n567,1700000000\n567,1700000000\n"
+        );
+    }
+
+    #[test]
+    fn sink_rejects_unknown_columns() {
+        let columns = ["start_ts".to_string(), "gl2gl_ms".to_string()];
+        let res = CsvSink::new(&temp_csv_path(), &scenarios(), "USD", Some(&columns));
+        assert_eq!(
+            res.err().map(|e| e.to_string()),
+            Some("Unknown CSV column gl2gl_ms".into())
+        );
+    }
+
+    /// Counts the recorded iterations and flushes, optionally failing every call
+    struct CountingSink {
+        fail: bool,
+        records: Arc<AtomicUsize>,
+        flushes: Arc<AtomicUsize>,
+    }
+
+    impl CountingSink {
+        fn new(fail: bool) -> Self {
+            Self {
+                fail,
+                records: Default::default(),
+                flushes: Default::default(),
+            }
+        }
+
+        fn result(&self) -> Result<()> {
+            match self.fail {
+                true => Err(anyhow!("Forced failure")),
+                false => Ok(()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Sink for CountingSink {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+
+        async fn record(&mut self, _iteration_res: &IterationResult) -> Result<()> {
+            self.records.fetch_add(1, Ordering::SeqCst);
+            self.result()
+        }
+
+        async fn flush(&mut self) -> Result<()> {
+            self.flushes.fetch_add(1, Ordering::SeqCst);
+            self.result()
+        }
+    }
+
+    #[tokio::test]
+    async fn writer_keeps_writing_after_a_sink_failed() {
+        let failing = CountingSink::new(true);
+        let working = CountingSink::new(false);
+        let counters = [
+            (failing.records.clone(), failing.flushes.clone()),
+            (working.records.clone(), working.flushes.clone()),
+        ];
+        let mut writer = SinkWriter::spawn(vec![Box::new(failing), Box::new(working)]).unwrap();
+        for _ in 0..3 {
+            let iteration_res = Arc::new(IterationResult::with_scenarios(vec![]));
+            writer.record(iteration_res).await.unwrap();
+        }
+        writer.finish().await.unwrap();
+
+        for (records, flushes) in counters {
+            assert_eq!(records.load(Ordering::SeqCst), 3);
+            assert_eq!(flushes.load(Ordering::SeqCst), 1);
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use rusqlite::{params, Connection};
+
+use crate::sinks::Sink;
+use crate::IterationResult;
+
+/// Stores the scenario results in a SQLite database, one row per scenario per iteration
+pub(crate) struct SqliteSink {
+    conn: Connection,
+}
+
+impl SqliteSink {
+    /// Open the database, creating it and the `measurements` table if needed
+    pub(crate) fn open(path: &str) -> Result<Self> {
+        let conn = Connection::open(path)?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS measurements (
+                start_ts INTEGER NOT NULL,
+                scenario TEXT NOT NULL,
+                duration_ms INTEGER,
+                status TEXT NOT NULL,
+                fee_msat INTEGER
+            )",
+            [],
+        )?;
+        Ok(Self { conn })
+    }
+}
+
+#[async_trait]
+impl Sink for SqliteSink {
+    fn name(&self) -> &'static str {
+        "SQLite"
+    }
+
+    async fn record(&mut self, iteration_res: &IterationResult) -> Result<()> {
+        let tx = self.conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT INTO measurements (start_ts, scenario, duration_ms, status, fee_msat)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+            )?;
+            for scenario in &iteration_res.scenarios {
+                let res = &scenario.measurement;
+                stmt.execute(params![
+                    iteration_res.start_ts,
+                    scenario.name,
+                    res.duration_ms(),
+                    res.status,
+                    res.fee_msat,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::scenarios::Measurement;
+use crate::{iso_timestamp, IterationResult};
+
+/// Upper bounds of the latency buckets, in seconds, if none are configured
+pub(crate) const DEFAULT_LATENCY_BUCKETS_SECS: [f64; 4] = [1.0, 2.0, 5.0, 10.0];
+
+/// Outcomes of one scenario, collected across iterations
+#[derive(Clone, Default, Deserialize, Serialize)]
+pub(crate) struct ScenarioStats {
+    /// Durations of the successful runs, in milliseconds
+    pub(crate) durations: Vec<u64>,
+    pub(crate) successes: u64,
+    /// Number of runs that succeeded or failed, not counting the skipped ones
+    pub(crate) total: u64,
+    /// Number of runs that were skipped, counted separately from the failures
+    #[serde(default)]
+    pub(crate) skipped: u64,
+    /// Number of failed runs since the last successful one
+    pub(crate) consecutive_failures: u64,
+    /// Number of successful runs per latency bucket of [Stats], the last one being the `+Inf`
+    /// overflow bucket
+    pub(crate) bucket_counts: Vec<u64>,
+}
+
+impl ScenarioStats {
+    /// Percentage of successful runs, if there were any runs
+    pub(crate) fn success_rate(&self) -> Option<f64> {
+        match self.total {
+            0 => None,
+            total => Some(self.successes as f64 / total as f64 * 100.0),
+        }
+    }
+
+    /// Ratio (0 to 1) of the runs that succeeded within the timeout, if there were any runs.
+    ///
+    /// Timeouts and other failures count as unavailable, while skipped runs are not counted at all.
+    pub(crate) fn availability(&self) -> Option<f64> {
+        match self.total {
+            0 => None,
+            total => Some(self.successes as f64 / total as f64),
+        }
+    }
+
+    /// Mean duration of the successful runs, if there were any
+    pub(crate) fn mean_ms(&self) -> Option<f64> {
+        match self.durations.len() {
+            0 => None,
+            len => Some(self.durations.iter().sum::<u64>() as f64 / len as f64),
+        }
+    }
+
+    /// The `p`-th percentile (0 to 100) duration of the successful runs, if there were any
+    pub(crate) fn percentile_ms(&self, p: f64) -> Option<u64> {
+        if self.durations.is_empty() {
+            return None;
+        }
+        let mut sorted = self.durations.clone();
+        sorted.sort_unstable();
+        Some(percentile(&sorted, p))
+    }
+}
+
+/// Outcomes of every scenario, collected across iterations
+#[derive(Clone, Deserialize, Serialize)]
+pub(crate) struct Stats {
+    /// Scenario names with their stats, in the order the scenarios were first seen
+    scenarios: Vec<(String, ScenarioStats)>,
+    /// Ascending upper bounds of the latency buckets, in seconds
+    bucket_bounds_secs: Vec<f64>,
+    /// Start timestamps of the first and last recorded iterations
+    first_ts: Option<u64>,
+    last_ts: Option<u64>,
+}
+
+impl Stats {
+    pub(crate) fn new(bucket_bounds_secs: Vec<f64>) -> Self {
+        Self {
+            scenarios: vec![],
+            bucket_bounds_secs,
+            first_ts: None,
+            last_ts: None,
+        }
+    }
+
+    /// Collect the outcomes of the scenarios of this iteration
+    pub(crate) fn record(&mut self, iteration_res: &IterationResult) {
+        self.first_ts.get_or_insert(iteration_res.start_ts);
+        self.last_ts = Some(iteration_res.start_ts);
+        for scenario in &iteration_res.scenarios {
+            for measurement in scenario.samples() {
+                self.record_measurement(&scenario.name, measurement);
+            }
+        }
+    }
+
+    fn record_measurement(&mut self, name: &str, measurement: &Measurement) {
+        if measurement.is_skipped() {
+            self.scenario_mut(name).skipped += 1;
+            return;
+        }
+        // An unverified payment has the duration of sending it, but still failed
+        let duration = measurement.duration.filter(|_| measurement.is_ok());
+        let bucket = duration.map(|duration| {
+            let secs = duration.as_secs_f64();
+            self.bucket_bounds_secs
+                .iter()
+                .position(|bound| secs <= *bound)
+                .unwrap_or(self.bucket_bounds_secs.len())
+        });
+        let scenario_stats = self.scenario_mut(name);
+        scenario_stats.total += 1;
+        match duration {
+            Some(duration) => {
+                let duration_ms = duration.as_millis() as u64;
+                scenario_stats.successes += 1;
+                scenario_stats.durations.push(duration_ms);
+                scenario_stats.consecutive_failures = 0;
+                if let Some(bucket) = bucket {
+                    scenario_stats.bucket_counts[bucket] += 1;
+                }
+            }
+            None => scenario_stats.consecutive_failures += 1,
+        }
+    }
+
+    pub(crate) fn bucket_bounds_secs(&self) -> &[f64] {
+        &self.bucket_bounds_secs
+    }
+
+    /// Scenario names with their stats, in the order the scenarios were first seen
+    pub(crate) fn scenarios(&self) -> &[(String, ScenarioStats)] {
+        &self.scenarios
+    }
+
+    fn scenario_mut(&mut self, name: &str) -> &mut ScenarioStats {
+        let idx = match self.scenarios.iter().position(|(n, _)| n == name) {
+            Some(idx) => idx,
+            None => {
+                let scenario_stats = ScenarioStats {
+                    bucket_counts: vec![0; self.bucket_bounds_secs.len() + 1],
+                    ..Default::default()
+                };
+                self.scenarios.push((name.into(), scenario_stats));
+                self.scenarios.len() - 1
+            }
+        };
+        &mut self.scenarios[idx].1
+    }
+
+    /// Log the success rate of every scenario so far
+    pub(crate) fn log_success_rates(&self) {
+        for (name, scenario_stats) in &self.scenarios {
+            if let Some(success_rate) = scenario_stats.success_rate() {
+                info!(
+                    "{name} success rate: {success_rate:.1}% ({}/{})",
+                    scenario_stats.successes, scenario_stats.total
+                );
+            }
+        }
+    }
+
+    /// Log the availability and the mean and p50/p90/p99 duration of every scenario, along with its
+    /// latency buckets
+    pub(crate) fn log_summary(&self) {
+        if let (Some(first_ts), Some(last_ts)) = (self.first_ts, self.last_ts) {
+            info!(
+                "Observed iterations from {} to {} ({}s)",
+                iso_timestamp(first_ts),
+                iso_timestamp(last_ts),
+                last_ts - first_ts
+            );
+        }
+        for (name, scenario_stats) in &self.scenarios {
+            match scenario_stats.availability() {
+                Some(availability) => info!(
+                    "{name}: availability {:.2}% ({}/{} runs succeeded within the timeout, {} skipped)",
+                    availability * 100.0,
+                    scenario_stats.successes,
+                    scenario_stats.total,
+                    scenario_stats.skipped
+                ),
+                None => {
+                    info!("{name}: all {} runs skipped", scenario_stats.skipped);
+                    continue;
+                }
+            }
+
+            let Some(mean) = scenario_stats.mean_ms() else {
+                info!("{name}: no successful runs");
+                continue;
+            };
+
+            let pct = |p| scenario_stats.percentile_ms(p).unwrap_or_default();
+            info!(
+                "{name}: mean {mean:.0}ms, p50 {}ms, p90 {}ms, p99 {}ms ({} successful runs)",
+                pct(50.0),
+                pct(90.0),
+                pct(99.0),
+                scenario_stats.durations.len()
+            );
+            info!(
+                "{name}: latency buckets {}",
+                self.format_buckets(scenario_stats)
+            );
+        }
+    }
+
+    /// The bucket counts of the scenario, like `0-1s: 3, 1-2s: 1, 2s-+Inf: 0`
+    fn format_buckets(&self, scenario_stats: &ScenarioStats) -> String {
+        let mut lower = 0.0;
+        let mut buckets = vec![];
+        for (bound, count) in self
+            .bucket_bounds_secs
+            .iter()
+            .zip(&scenario_stats.bucket_counts)
+        {
+            buckets.push(format!("{lower}-{bound}s: {count}"));
+            lower = *bound;
+        }
+        let overflow = scenario_stats
+            .bucket_counts
+            .last()
+            .copied()
+            .unwrap_or_default();
+        buckets.push(format!("{lower}s-+Inf: {overflow}"));
+        buckets.join(", ")
+    }
+
+    /// Stats of the scenario with this name, if it ran at least once
+    pub(crate) fn scenario(&self, name: &str) -> Option<&ScenarioStats> {
+        self.scenarios
+            .iter()
+            .find_map(|(n, scenario_stats)| (n == name).then_some(scenario_stats))
+    }
+}
+
+/// The `p`-th percentile (0 to 100) of the `sorted` values, linearly interpolated between the
+/// closest ranks. Returns 0 for an empty slice.
+fn percentile(sorted: &[u64], p: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+
+    let rank = p.clamp(0.0, 100.0) / 100.0 * (sorted.len() - 1) as f64;
+    let lower = sorted[rank.floor() as usize] as f64;
+    let upper = sorted[rank.ceil() as usize] as f64;
+    (lower + (upper - lower) * rank.fract()).round() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentile_of_empty_slice_is_zero() {
+        assert_eq!(percentile(&[], 50.0), 0);
+    }
+
+    #[test]
+    fn percentile_of_single_element_is_that_element() {
+        for p in [0.0, 50.0, 90.0, 100.0] {
+            assert_eq!(percentile(&[42], p), 42);
+        }
+    }
+
+    #[test]
+    fn percentile_bounds_are_min_and_max() {
+        let sorted = [10, 20, 30, 40];
+        assert_eq!(percentile(&sorted, 0.0), 10);
+        assert_eq!(percentile(&sorted, 100.0), 40);
+        // Out of range percentiles are clamped
+        assert_eq!(percentile(&sorted, -5.0), 10);
+        assert_eq!(percentile(&sorted, 150.0), 40);
+    }
+
+    #[test]
+    fn percentile_interpolates_between_ranks() {
+        let sorted = [10, 20, 30, 40];
+        // Rank 1.5, halfway between 20 and 30
+        assert_eq!(percentile(&sorted, 50.0), 25);
+        // Rank 2.7, 70% of the way from 30 to 40
+        assert_eq!(percentile(&sorted, 90.0), 37);
+        // Exactly on rank 1
+        assert_eq!(percentile(&[10, 20, 30, 40, 50], 25.0), 20);
+    }
+}
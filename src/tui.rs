@@ -0,0 +1,212 @@
+use std::collections::VecDeque;
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use anyhow::Result;
+use log::error;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Style};
+use ratatui::widgets::{Block, Borders, Paragraph, Sparkline};
+use ratatui::{DefaultTerminal, Frame};
+use tokio_util::sync::CancellationToken;
+
+use crate::IterationResult;
+
+/// How many of the latest runs of a scenario are shown in its sparkline and success rate
+const HISTORY_LEN: usize = 30;
+
+/// How often the render loop checks for key presses and new results
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Outcome of one run of a scenario, as shown on the dashboard
+struct Run {
+    duration_ms: Option<u64>,
+    is_ok: bool,
+    status: String,
+}
+
+/// The latest runs of one scenario, newest last
+struct ScenarioHistory {
+    name: String,
+    runs: VecDeque<Run>,
+}
+
+/// Live terminal dashboard with the latest results of every scenario.
+///
+/// It's rendered on its own thread, so it stays responsive while the iterations run. Pressing `q`
+/// or Ctrl-C cancels the `shutdown` token, like a shutdown signal would.
+///
+/// Dropping it closes the dashboard and restores the terminal, also when exiting with an error.
+pub(crate) struct Dashboard {
+    updates: Option<mpsc::Sender<Vec<(String, Run)>>>,
+    handle: Option<JoinHandle<Result<()>>>,
+}
+
+impl Dashboard {
+    pub(crate) fn start(shutdown: CancellationToken) -> Self {
+        let (updates_tx, updates_rx) = mpsc::channel();
+        let handle = std::thread::spawn(move || {
+            let mut terminal = ratatui::init();
+            let res = render_loop(&mut terminal, updates_rx, shutdown);
+            ratatui::restore();
+            res
+        });
+        Self {
+            updates: Some(updates_tx),
+            handle: Some(handle),
+        }
+    }
+
+    /// Show the results of the iteration
+    pub(crate) fn update(&self, iteration_res: &IterationResult) {
+        let runs = iteration_res
+            .scenarios
+            .iter()
+            .filter(|scenario| !scenario.measurement.is_skipped())
+            .map(|scenario| {
+                let run = Run {
+                    duration_ms: scenario.measurement.duration_ms(),
+                    is_ok: scenario.measurement.is_ok(),
+                    status: scenario.measurement.status.clone(),
+                };
+                (scenario.name.clone(), run)
+            })
+            .collect();
+        if let Some(updates) = &self.updates {
+            // Only fails if the dashboard was already quit
+            let _ = updates.send(runs);
+        }
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        // Closing the channel ends the render loop
+        self.updates.take();
+        if let Some(handle) = self.handle.take() {
+            match handle.join() {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => error!("Dashboard failed: {e}"),
+                Err(_) => error!("Dashboard thread panicked"),
+            }
+        }
+    }
+}
+
+fn render_loop(
+    terminal: &mut DefaultTerminal,
+    updates: mpsc::Receiver<Vec<(String, Run)>>,
+    shutdown: CancellationToken,
+) -> Result<()> {
+    let mut scenarios: Vec<ScenarioHistory> = vec![];
+    loop {
+        loop {
+            match updates.try_recv() {
+                Ok(runs) => apply_update(&mut scenarios, runs),
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => return Ok(()),
+            }
+        }
+
+        terminal.draw(|frame| draw(frame, &scenarios, shutdown.is_cancelled()))?;
+
+        if event::poll(POLL_INTERVAL)? {
+            if let Event::Key(key) = event::read()? {
+                let ctrl_c =
+                    key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL);
+                if key.kind == KeyEventKind::Press && (key.code == KeyCode::Char('q') || ctrl_c) {
+                    shutdown.cancel();
+                }
+            }
+        }
+    }
+}
+
+fn apply_update(scenarios: &mut Vec<ScenarioHistory>, runs: Vec<(String, Run)>) {
+    for (name, run) in runs {
+        let idx = match scenarios.iter().position(|s| s.name == name) {
+            Some(idx) => idx,
+            None => {
+                scenarios.push(ScenarioHistory {
+                    name,
+                    runs: VecDeque::new(),
+                });
+                scenarios.len() - 1
+            }
+        };
+        let history = &mut scenarios[idx];
+        history.runs.push_back(run);
+        if history.runs.len() > HISTORY_LEN {
+            history.runs.pop_front();
+        }
+    }
+}
+
+fn draw(frame: &mut Frame, scenarios: &[ScenarioHistory], stopping: bool) {
+    let [header_area, scenarios_area] =
+        Layout::vertical([Constraint::Length(1), Constraint::Min(0)]).areas(frame.area());
+
+    let header = if stopping {
+        "sdk_pulse - stopping after the current iteration"
+    } else {
+        "sdk_pulse - press q to quit"
+    };
+    frame.render_widget(Paragraph::new(header), header_area);
+
+    if scenarios.is_empty() {
+        frame.render_widget(
+            Paragraph::new("Waiting for the first iteration..."),
+            scenarios_area,
+        );
+        return;
+    }
+
+    let rows =
+        Layout::vertical(scenarios.iter().map(|_| Constraint::Length(3))).split(scenarios_area);
+    for (history, area) in scenarios.iter().zip(rows.iter()) {
+        draw_scenario(frame, history, *area);
+    }
+}
+
+fn draw_scenario(frame: &mut Frame, history: &ScenarioHistory, area: Rect) {
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(history.name.as_str());
+    let [stats_area, sparkline_area] =
+        Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .areas(block.inner(area));
+    frame.render_widget(block, area);
+
+    let Some(latest) = history.runs.back() else {
+        return;
+    };
+    let latest_ms = latest
+        .duration_ms
+        .map(|ms| format!("{ms} ms"))
+        .unwrap_or("-".into());
+    let successes = history.runs.iter().filter(|run| run.is_ok).count();
+    let success_rate = successes as f64 / history.runs.len() as f64 * 100.0;
+    let color = if latest.is_ok {
+        Color::Green
+    } else {
+        Color::Red
+    };
+    let stats = format!(
+        "latest {latest_ms} | {success_rate:.1}% ok of last {} | {}",
+        history.runs.len(),
+        latest.status
+    );
+    frame.render_widget(
+        Paragraph::new(stats).style(Style::default().fg(color)),
+        stats_area,
+    );
+
+    let durations: Vec<u64> = history
+        .runs
+        .iter()
+        .map(|run| run.duration_ms.unwrap_or_default())
+        .collect();
+    frame.render_widget(Sparkline::default().data(&durations), sparkline_area);
+}